@@ -0,0 +1,446 @@
+use nom::bytes::complete::tag;
+use nom::character::complete::anychar;
+use nom::character::complete::char;
+use nom::character::complete::hex_digit0;
+use nom::character::complete::one_of;
+use nom::combinator::map;
+use nom::combinator::map_opt;
+use nom::combinator::map_res;
+use nom::combinator::opt;
+use nom::multi::count;
+use nom::sequence::delimited;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use std::fmt;
+
+use crate::ParseInput;
+use crate::ParseResult;
+use crate::SignError;
+
+/// Colour of a single dot in a DOTS picture.
+#[repr(u8)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, FromPrimitive)]
+pub enum DotColor {
+    Off = 0x30,
+    Red = 0x31,
+    Green = 0x32,
+    Amber = 0x33,
+    DimRed = 0x34,
+    DimGreen = 0x35,
+    Brown = 0x36,
+    Orange = 0x37,
+    Yellow = 0x38,
+}
+
+impl DotColor {
+    fn parse(input: ParseInput) -> ParseResult<Self> {
+        map_opt(one_of("012345678"), |x| DotColor::from_u8(x as u8))(input)
+    }
+}
+
+/// Colour of a single dot in an RGB DOTS picture.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct RgbColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl RgbColor {
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    fn parse(input: ParseInput) -> ParseResult<Self> {
+        // parse_size(2) is at most 0xFF, so the casts can't truncate
+        map(
+            tuple((parse_size(2), parse_size(2), parse_size(2))),
+            |(red, green, blue)| RgbColor::new(red as u8, green as u8, blue as u8),
+        )(input)
+    }
+}
+
+/// A picture made of rows of coloured dots. Every row is the same width.
+///
+/// Small and large dots pictures use the sign's fixed palette of [`DotColor`]s, RGB dots pictures use [`RgbColor`]s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitmap<P = DotColor> {
+    rows: Vec<Vec<P>>,
+}
+
+impl<P> Bitmap<P> {
+    /// Creates a [`Bitmap`].
+    ///
+    /// # Arguments
+    /// * `rows`: The dots in each row, from the top.
+    ///
+    /// # Returns
+    /// The bitmap, or an error if the rows aren't all the same width.
+    pub fn new(rows: Vec<Vec<P>>) -> Result<Self, SignError> {
+        let width = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(SignError::EncodingError(
+                "every row of a bitmap must be the same width".to_string(),
+            ));
+        }
+        Ok(Self { rows })
+    }
+
+    /// Checks the bitmap fits in a picture of the given kind.
+    ///
+    /// # Arguments
+    /// * `max`: Most dots the picture can have in either direction.
+    /// * `kind`: Kind of picture, for the error message.
+    fn check_size(&self, max: usize, kind: &str) -> Result<(), SignError> {
+        if self.height() > max || self.width() > max {
+            return Err(SignError::EncodingError(format!(
+                "a {}x{} bitmap is too big for a {kind}",
+                self.height(),
+                self.width()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The dots in each row, from the top.
+    pub fn rows(&self) -> &[Vec<P>] {
+        &self.rows
+    }
+
+    /// Number of rows of dots.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Number of dots in each row.
+    pub fn width(&self) -> usize {
+        self.rows.first().map_or(0, Vec::len)
+    }
+
+    /// Encodes the dots, each row followed by a carriage return.
+    ///
+    /// # Arguments
+    /// * `encode_dot`: Appends the encoding of a single dot.
+    fn encode_rows(&self, encode_dot: impl Fn(&P, &mut Vec<u8>)) -> Vec<u8> {
+        let mut res = vec![];
+        for row in &self.rows {
+            for dot in row {
+                encode_dot(dot, &mut res);
+            }
+            res.push(0x0D);
+        }
+        res
+    }
+
+    /// Parses rows of dots of a known size.
+    ///
+    /// # Arguments
+    /// * `height`: Number of rows.
+    /// * `width`: Number of dots in each row.
+    /// * `parse_dot`: Parses a single dot.
+    fn parse_rows(
+        height: usize,
+        width: usize,
+        parse_dot: fn(ParseInput) -> ParseResult<P>,
+    ) -> impl Fn(ParseInput) -> ParseResult<Self> {
+        move |input| {
+            let (remain, rows) = count(
+                terminated(count(parse_dot, width), char(0x0D.into())),
+                height,
+            )(input)?;
+            Ok((remain, Bitmap { rows }))
+        }
+    }
+}
+
+/// Appends the encoding of a dot from the sign's palette.
+fn encode_dot_color(dot: &DotColor, res: &mut Vec<u8>) {
+    res.push(*dot as u8);
+}
+
+/// Parses a size given as ASCII hex digits.
+///
+/// # Arguments
+/// * `digits`: Number of digits in the size.
+fn parse_size(digits: usize) -> impl Fn(ParseInput) -> ParseResult<usize> {
+    move |input| {
+        map_res(count(one_of("0123456789ABCDEFabcdef"), digits), |x| {
+            usize::from_str_radix(&x.iter().collect::<String>(), 16)
+        })(input)
+    }
+}
+
+/// Writes a picture to a SMALL DOTS PICTURE file, to be shown in a message with the call dots picture control code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteSmallDotsPicture {
+    pub label: char,
+    pub bitmap: Bitmap,
+}
+
+impl WriteSmallDotsPicture {
+    const COMMANDCODE: u8 = 0x49;
+
+    /// Creates a [`WriteSmallDotsPicture`].
+    ///
+    /// # Arguments
+    /// * `label`: Label of the picture file.
+    /// * `bitmap`: The picture.
+    ///
+    /// # Returns
+    /// The command, or an error if the picture is over 255 dots in either direction.
+    pub fn new(label: char, bitmap: Bitmap) -> Result<Self, SignError> {
+        bitmap.check_size(0xFF, "small dots picture")?;
+        Ok(Self { label, bitmap })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = vec![Self::COMMANDCODE, self.label as u8];
+        res.append(
+            &mut format!(
+                "{height:02X}{width:02X}",
+                height = self.bitmap.height(),
+                width = self.bitmap.width()
+            )
+            .into_bytes(),
+        );
+        res.append(&mut self.bitmap.encode_rows(encode_dot_color));
+        res
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, (label, height, width)) = preceded(
+            tag([0x02, Self::COMMANDCODE]),
+            tuple((anychar, parse_size(2), parse_size(2))),
+        )(input)?;
+        let (remain, bitmap) = terminated(
+            Bitmap::parse_rows(height, width, DotColor::parse),
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // checksum, parsed but discarded
+        )(remain)?;
+
+        Ok((remain, WriteSmallDotsPicture { label, bitmap }))
+    }
+}
+
+impl fmt::Display for WriteSmallDotsPicture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WriteSmallDotsPicture {} {}x{}",
+            self.label,
+            self.bitmap.height(),
+            self.bitmap.width()
+        )
+    }
+}
+
+/// Asks the sign for a SMALL DOTS PICTURE file. The sign answers with a [`WriteSmallDotsPicture`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadSmallDotsPicture {
+    pub label: char,
+}
+
+impl ReadSmallDotsPicture {
+    const COMMANDCODE: u8 = 0x4A;
+
+    pub fn new(label: char) -> Self {
+        Self { label }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![Self::COMMANDCODE, self.label as u8]
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, label) = delimited(
+            tag([0x02, Self::COMMANDCODE]),
+            anychar,                                                // label
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // optional checksum, discarded
+        )(input)?;
+
+        Ok((remain, ReadSmallDotsPicture::new(label)))
+    }
+}
+
+impl fmt::Display for ReadSmallDotsPicture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReadSmallDotsPicture {}", self.label)
+    }
+}
+
+/// Writes a picture to a LARGE DOTS PICTURE file. Full matrix signs use these for pictures bigger than a small dots
+/// picture can hold.
+///
+/// Experimental: the header is a guess and may well be rejected by a real sign, so this is only built with the
+/// `experimental` feature.
+#[cfg(feature = "experimental")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteLargeDotsPicture {
+    pub label: char,
+    pub bitmap: Bitmap,
+}
+
+#[cfg(feature = "experimental")]
+impl WriteLargeDotsPicture {
+    const COMMANDCODE: u8 = 0x4D;
+
+    /// Creates a [`WriteLargeDotsPicture`].
+    ///
+    /// # Arguments
+    /// * `label`: Label of the picture file.
+    /// * `bitmap`: The picture.
+    ///
+    /// # Returns
+    /// The command, or an error if the picture is over 65535 dots in either direction.
+    pub fn new(label: char, bitmap: Bitmap) -> Result<Self, SignError> {
+        bitmap.check_size(0xFFFF, "large dots picture")?;
+        Ok(Self { label, bitmap })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = vec![Self::COMMANDCODE, self.label as u8];
+        //TODO confirm against a full matrix sign, the spec's examples only show the height and width widening to four
+        // hex digits compared to small dots pictures
+        res.append(
+            &mut format!(
+                "{height:04X}{width:04X}",
+                height = self.bitmap.height(),
+                width = self.bitmap.width()
+            )
+            .into_bytes(),
+        );
+        res.append(&mut self.bitmap.encode_rows(encode_dot_color));
+        res
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, (label, height, width)) = preceded(
+            tag([0x02, Self::COMMANDCODE]),
+            tuple((anychar, parse_size(4), parse_size(4))),
+        )(input)?;
+        let (remain, bitmap) = terminated(
+            Bitmap::parse_rows(height, width, DotColor::parse),
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // checksum, parsed but discarded
+        )(remain)?;
+
+        Ok((remain, WriteLargeDotsPicture { label, bitmap }))
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl fmt::Display for WriteLargeDotsPicture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WriteLargeDotsPicture {} {}x{}",
+            self.label,
+            self.bitmap.height(),
+            self.bitmap.width()
+        )
+    }
+}
+
+/// Asks the sign for a LARGE DOTS PICTURE file. The sign answers with a [`WriteLargeDotsPicture`].
+#[cfg(feature = "experimental")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadLargeDotsPicture {
+    pub label: char,
+}
+
+#[cfg(feature = "experimental")]
+impl ReadLargeDotsPicture {
+    const COMMANDCODE: u8 = 0x4E;
+
+    pub fn new(label: char) -> Self {
+        Self { label }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![Self::COMMANDCODE, self.label as u8]
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, label) = delimited(
+            tag([0x02, Self::COMMANDCODE]),
+            anychar,                                                // label
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // optional checksum, discarded
+        )(input)?;
+
+        Ok((remain, ReadLargeDotsPicture::new(label)))
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl fmt::Display for ReadLargeDotsPicture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReadLargeDotsPicture {}", self.label)
+    }
+}
+
+/// Writes a full colour picture to an RGB DOTS PICTURE file, for colour signs such as the AlphaEclipse range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteRgbDotsPicture {
+    pub label: char,
+    pub bitmap: Bitmap<RgbColor>,
+}
+
+impl WriteRgbDotsPicture {
+    const COMMANDCODE: u8 = 0x4B;
+
+    /// Creates a [`WriteRgbDotsPicture`].
+    ///
+    /// # Arguments
+    /// * `label`: Label of the picture file.
+    /// * `bitmap`: The picture.
+    ///
+    /// # Returns
+    /// The command, or an error if the picture is over 65535 dots in either direction.
+    pub fn new(label: char, bitmap: Bitmap<RgbColor>) -> Result<Self, SignError> {
+        bitmap.check_size(0xFFFF, "RGB dots picture")?;
+        Ok(Self { label, bitmap })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = vec![Self::COMMANDCODE, self.label as u8];
+        //TODO the spec also describes a run length compressed form of the dots, only the uncompressed one is supported
+        res.append(
+            &mut format!(
+                "{height:04X}{width:04X}",
+                height = self.bitmap.height(),
+                width = self.bitmap.width()
+            )
+            .into_bytes(),
+        );
+        res.append(&mut self.bitmap.encode_rows(|dot, res| {
+            res.extend(format!("{:02X}{:02X}{:02X}", dot.red, dot.green, dot.blue).into_bytes())
+        }));
+        res
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, (label, height, width)) = preceded(
+            tag([0x02, Self::COMMANDCODE]),
+            tuple((anychar, parse_size(4), parse_size(4))),
+        )(input)?;
+        let (remain, bitmap) = terminated(
+            Bitmap::parse_rows(height, width, RgbColor::parse),
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // checksum, parsed but discarded
+        )(remain)?;
+
+        Ok((remain, WriteRgbDotsPicture { label, bitmap }))
+    }
+}
+
+impl fmt::Display for WriteRgbDotsPicture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WriteRgbDotsPicture {} {}x{}",
+            self.label,
+            self.bitmap.height(),
+            self.bitmap.width()
+        )
+    }
+}
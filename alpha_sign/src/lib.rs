@@ -11,8 +11,11 @@ use nom::{
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use std::str;
+use std::{fmt, str, str::FromStr};
 
+pub mod dots;
+pub mod read_special;
+pub mod response;
 pub mod text;
 pub mod write_special;
 
@@ -22,6 +25,127 @@ pub type ParseResult<'a, O> =
 
 pub const BROADCAST: u8 = 0x00;
 
+/// Versions of the Alpha protocol. Each version understands everything the ones before it do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    /// The original protocol, as spoken by BetaBrites and older Alpha signs.
+    Alpha1,
+    Alpha2,
+    Alpha3,
+}
+
+impl ProtocolVersion {
+    pub const LATEST: ProtocolVersion = ProtocolVersion::Alpha3;
+}
+
+/// What a sign is able to do, so that features it doesn't have can be swapped for ones it does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolProfile {
+    pub version: ProtocolVersion,
+    /// Whether the sign has the special transition modes, e.g. twinkle and sparkle.
+    pub special_modes: bool,
+    /// Most characters the sign's largest text file can hold, if known.
+    pub max_text_len: Option<u16>,
+    /// Whether the sign has a DOTS PICTURE file in memory to show pictures from.
+    pub dots_pictures: bool,
+}
+
+impl ProtocolProfile {
+    /// The profile of a typical sign speaking the given version of the protocol.
+    pub fn for_version(version: ProtocolVersion) -> Self {
+        Self {
+            version,
+            //TODO confirm, BetaBrites and other Alpha 1.0 signs seem to ignore the special modes
+            special_modes: version >= ProtocolVersion::Alpha2,
+            max_text_len: None,
+            dots_pictures: true,
+        }
+    }
+
+    /// The profile of a sign with the given memory configuration, as read back with
+    /// [`ReadSpecial::MemoryConfiguration`](read_special::ReadSpecial::MemoryConfiguration).
+    ///
+    /// # Arguments
+    /// * `version`: Version of the protocol the sign speaks.
+    /// * `configurations`: How the sign's memory is laid out into files.
+    pub fn from_memory_configuration(
+        version: ProtocolVersion,
+        configurations: &[write_special::MemoryConfiguration],
+    ) -> Self {
+        let text_sizes: Vec<u16> = configurations
+            .iter()
+            .filter_map(|configuration| match configuration.file_type {
+                write_special::FileType::Text { size, .. } => Some(size),
+                _ => None,
+            })
+            .collect();
+        Self {
+            // A file of size 0 has the rest of memory, however much that is
+            max_text_len: if text_sizes.contains(&0) {
+                None
+            } else {
+                text_sizes.into_iter().max()
+            },
+            dots_pictures: configurations.iter().any(|configuration| {
+                matches!(
+                    configuration.file_type,
+                    write_special::FileType::Dots { .. }
+                )
+            }),
+            ..Self::for_version(version)
+        }
+    }
+
+    /// Whether the sign can show text with the given transition mode.
+    ///
+    /// # Arguments
+    /// * `mode`: The transition mode.
+    pub fn supports_mode(&self, mode: text::TransitionMode) -> bool {
+        !mode.is_special() || self.special_modes
+    }
+
+    /// Swaps anything in some text that the sign can't do for something it can.
+    ///
+    /// # Arguments
+    /// * `text`: The text to downgrade.
+    ///
+    /// # Returns
+    /// The downgraded text, and a description of each change made to it.
+    pub fn downgrade(&self, mut text: text::WriteText) -> (text::WriteText, Vec<String>) {
+        let mut changes = vec![];
+        if !self.supports_mode(text.mode) {
+            changes.push(format!(
+                "sign doesn't have the {:?} mode, using Hold instead",
+                text.mode
+            ));
+            text.mode = text::TransitionMode::Hold;
+        }
+        if let Some(max_text_len) = self.max_text_len {
+            let len = text.message.chars().count();
+            if len > usize::from(max_text_len) {
+                changes.push(format!(
+                    "text is {len} characters but the sign's largest text file holds {max_text_len}, cutting it short"
+                ));
+                text.message = text.message.chars().take(max_text_len.into()).collect();
+            }
+        }
+        (text, changes)
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = text::UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "1.0" | "alpha1" => Ok(ProtocolVersion::Alpha1),
+            "2" | "2.0" | "alpha2" => Ok(ProtocolVersion::Alpha2),
+            "3" | "3.0" | "alpha3" => Ok(ProtocolVersion::Alpha3),
+            _ => Err(text::UnknownName {}),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SignSelector {
     pub sign_type: SignType,
@@ -60,11 +184,27 @@ impl SignSelector {
     }
 }
 
+impl fmt::Display for SignSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}:{:02X}", self.sign_type, self.address)
+    }
+}
+
 #[derive(Debug)]
 pub enum SignError {
     EncodingError(String),
 }
 
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignError::EncodingError(e) => write!(f, "encoding error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SignError {}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct Packet {
     pub selectors: Vec<SignSelector>,
@@ -81,6 +221,24 @@ impl Packet {
     }
 
     pub fn encode(&self) -> Result<Vec<u8>, SignError> {
+        self.encode_for(ProtocolVersion::LATEST)
+    }
+
+    /// Encodes the packet for a sign speaking the given version of the protocol.
+    ///
+    /// # Arguments
+    /// * `version`: Version of the protocol the sign speaks.
+    ///
+    /// # Returns
+    /// The encoded packet, or an error if any of the commands aren't in that version of the protocol.
+    pub fn encode_for(&self, version: ProtocolVersion) -> Result<Vec<u8>, SignError> {
+        if let Some(command) = self.commands.iter().find(|c| c.min_version() > version) {
+            return Err(SignError::EncodingError(format!(
+                "{command} needs {:?} but the sign speaks {version:?}",
+                command.min_version()
+            )));
+        }
+
         let mut res: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x01]; //start of transmission
         for selector in &self.selectors {
             res.push(selector.sign_type as u8);
@@ -128,11 +286,37 @@ impl Packet {
     }
 }
 
+/// Decodes a packet into a single human readable line, e.g. for logging what is sent to the sign.
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, selector) in self.selectors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{selector}")?;
+        }
+        for command in &self.commands {
+            write!(f, " | {command}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Command {
     WriteText(text::WriteText),
     ReadText(text::ReadText),
+    WriteString(text::WriteString),
+    ReadString(text::ReadString),
+    WriteSmallDotsPicture(dots::WriteSmallDotsPicture),
+    ReadSmallDotsPicture(dots::ReadSmallDotsPicture),
+    #[cfg(feature = "experimental")]
+    WriteLargeDotsPicture(dots::WriteLargeDotsPicture),
+    #[cfg(feature = "experimental")]
+    ReadLargeDotsPicture(dots::ReadLargeDotsPicture),
+    WriteRgbDotsPicture(dots::WriteRgbDotsPicture),
     WriteSpecial(write_special::WriteSpecial),
+    ReadSpecial(read_special::ReadSpecial),
 }
 
 impl Command {
@@ -140,7 +324,37 @@ impl Command {
         match self {
             Command::WriteText(write_text) => write_text.encode(),
             Command::ReadText(read_text) => read_text.encode(),
+            Command::WriteString(write_string) => write_string.encode(),
+            Command::ReadString(read_string) => read_string.encode(),
+            Command::WriteSmallDotsPicture(write_dots) => write_dots.encode(),
+            Command::ReadSmallDotsPicture(read_dots) => read_dots.encode(),
+            #[cfg(feature = "experimental")]
+            Command::WriteLargeDotsPicture(write_dots) => write_dots.encode(),
+            #[cfg(feature = "experimental")]
+            Command::ReadLargeDotsPicture(read_dots) => read_dots.encode(),
+            Command::WriteRgbDotsPicture(write_dots) => write_dots.encode(),
             Command::WriteSpecial(write_special) => write_special.encode(),
+            Command::ReadSpecial(read_special) => read_special.encode(),
+        }
+    }
+
+    /// The oldest version of the protocol that has this command.
+    pub fn min_version(&self) -> ProtocolVersion {
+        match self {
+            Command::WriteText(_)
+            | Command::ReadText(_)
+            | Command::WriteString(_)
+            | Command::ReadString(_)
+            | Command::WriteSmallDotsPicture(_)
+            | Command::ReadSmallDotsPicture(_) => ProtocolVersion::Alpha1,
+            #[cfg(feature = "experimental")]
+            Command::WriteLargeDotsPicture(_) | Command::ReadLargeDotsPicture(_) => {
+                ProtocolVersion::Alpha1
+            }
+            //TODO confirm, RGB dots pictures only seem to be documented for AlphaEclipse signs
+            Command::WriteRgbDotsPicture(_) => ProtocolVersion::Alpha3,
+            Command::WriteSpecial(write_special) => write_special.min_version(),
+            Command::ReadSpecial(_) => ProtocolVersion::Alpha1,
         }
     }
 
@@ -148,18 +362,71 @@ impl Command {
         match self {
             Command::WriteText(_) => false,
             Command::ReadText(_) => true,
+            Command::WriteString(_) => false,
+            Command::ReadString(_) => true,
+            Command::WriteSmallDotsPicture(_) => false,
+            Command::ReadSmallDotsPicture(_) => true,
+            #[cfg(feature = "experimental")]
+            Command::WriteLargeDotsPicture(_) => false,
+            #[cfg(feature = "experimental")]
+            Command::ReadLargeDotsPicture(_) => true,
+            Command::WriteRgbDotsPicture(_) => false,
             Command::WriteSpecial(_) => false,
+            Command::ReadSpecial(_) => true,
         }
     }
 
     pub fn parse(input: ParseInput) -> ParseResult<Self> {
-        Ok(alt((
-            map(text::WriteText::parse, |x| Command::WriteText(x)),
-            map(text::ReadText::parse, |x| Command::ReadText(x)),
-            map(write_special::WriteSpecial::parse, |x| {
-                Command::WriteSpecial(x)
-            }),
-        ))(input)?)
+        alt((
+            map(text::WriteText::parse, Command::WriteText),
+            map(text::ReadText::parse, Command::ReadText),
+            map(text::WriteString::parse, Command::WriteString),
+            map(text::ReadString::parse, Command::ReadString),
+            map(
+                dots::WriteSmallDotsPicture::parse,
+                Command::WriteSmallDotsPicture,
+            ),
+            map(
+                dots::ReadSmallDotsPicture::parse,
+                Command::ReadSmallDotsPicture,
+            ),
+            #[cfg(feature = "experimental")]
+            map(
+                dots::WriteLargeDotsPicture::parse,
+                Command::WriteLargeDotsPicture,
+            ),
+            #[cfg(feature = "experimental")]
+            map(
+                dots::ReadLargeDotsPicture::parse,
+                Command::ReadLargeDotsPicture,
+            ),
+            map(
+                dots::WriteRgbDotsPicture::parse,
+                Command::WriteRgbDotsPicture,
+            ),
+            map(write_special::WriteSpecial::parse, Command::WriteSpecial),
+            map(read_special::ReadSpecial::parse, Command::ReadSpecial),
+        ))(input)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::WriteText(write_text) => write!(f, "{write_text}"),
+            Command::ReadText(read_text) => write!(f, "{read_text}"),
+            Command::WriteString(write_string) => write!(f, "{write_string}"),
+            Command::ReadString(read_string) => write!(f, "{read_string}"),
+            Command::WriteSmallDotsPicture(write_dots) => write!(f, "{write_dots}"),
+            Command::ReadSmallDotsPicture(read_dots) => write!(f, "{read_dots}"),
+            #[cfg(feature = "experimental")]
+            Command::WriteLargeDotsPicture(write_dots) => write!(f, "{write_dots}"),
+            #[cfg(feature = "experimental")]
+            Command::ReadLargeDotsPicture(read_dots) => write!(f, "{read_dots}"),
+            Command::WriteRgbDotsPicture(write_dots) => write!(f, "{write_dots}"),
+            Command::WriteSpecial(write_special) => write!(f, "WriteSpecial {write_special:?}"),
+            Command::ReadSpecial(read_special) => write!(f, "ReadSpecial {read_special:?}"),
+        }
     }
 }
 
@@ -215,3 +482,26 @@ pub enum SignType {
     TemperatureProbe = 0x79,
     AllSignsWithMemoryConfiguredFor26Files = 0x7a,
 }
+
+impl SignType {
+    /// Whether this type addresses a group of signs rather than a particular model.
+    pub fn is_group(&self) -> bool {
+        matches!(
+            self,
+            SignType::SignWithVisualVerification
+                | SignType::ResponsePacket
+                | SignType::OneLineSign
+                | SignType::TwoLineSign
+                | SignType::AllSigns
+                | SignType::All
+                | SignType::AllSignsWithMemoryConfiguredFor26Files
+        )
+    }
+
+    /// All the sign types that address a particular model of sign.
+    pub fn models() -> impl Iterator<Item = SignType> {
+        (0x20..=0x7f)
+            .filter_map(SignType::from_u8)
+            .filter(|sign_type| !sign_type.is_group())
+    }
+}
@@ -0,0 +1,71 @@
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::char;
+use nom::character::complete::hex_digit0;
+use nom::combinator::opt;
+use nom::combinator::value;
+use nom::multi::count;
+use nom::sequence::delimited;
+use nom::sequence::preceded;
+
+use crate::ParseInput;
+use crate::ParseResult;
+
+/// Asks the sign for the state of one of its special functions. The sign answers with the matching
+/// [`WriteSpecial`](crate::write_special::WriteSpecial), e.g. [`ReadSpecial::Time`] is answered with a
+/// [`SetTime`](crate::write_special::SetTime). [`Response`](crate::response::Response) decodes the answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadSpecial {
+    /// The time of day.
+    Time,
+    /// Whether the speaker is on.
+    SpeakerStatus,
+    /// How memory is laid out into files.
+    MemoryConfiguration,
+    /// The day of the week.
+    DayOfWeek,
+    /// Whether the time is shown in 24 hour format.
+    TimeFormat,
+    /// When each text file is shown.
+    RunTimeTable,
+    /// Errors the sign has seen in what was sent to it, answered with a
+    /// [`SerialErrorStatus`](crate::write_special::SerialErrorStatus).
+    SerialErrorStatus,
+}
+
+impl ReadSpecial {
+    const COMMANDCODE: u8 = 0x46;
+
+    /// The special function label, which is the same as the one used to set the function.
+    fn special_label(&self) -> u8 {
+        match self {
+            ReadSpecial::Time => 0x20,
+            ReadSpecial::SpeakerStatus => 0x21,
+            ReadSpecial::MemoryConfiguration => 0x24,
+            ReadSpecial::DayOfWeek => 0x26,
+            ReadSpecial::TimeFormat => 0x27,
+            ReadSpecial::RunTimeTable => 0x29,
+            ReadSpecial::SerialErrorStatus => 0x2A,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![Self::COMMANDCODE, self.special_label()]
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        delimited(
+            tag([0x02, Self::COMMANDCODE]),
+            alt((
+                value(ReadSpecial::Time, char(0x20.into())),
+                value(ReadSpecial::SpeakerStatus, char(0x21.into())),
+                value(ReadSpecial::MemoryConfiguration, char(0x24.into())),
+                value(ReadSpecial::DayOfWeek, char(0x26.into())),
+                value(ReadSpecial::TimeFormat, char(0x27.into())),
+                value(ReadSpecial::RunTimeTable, char(0x29.into())),
+                value(ReadSpecial::SerialErrorStatus, char(0x2A.into())),
+            )),
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // optional checksum, discarded
+        )(input)
+    }
+}
@@ -0,0 +1,92 @@
+use time::{Time, Weekday};
+
+use crate::dots::WriteSmallDotsPicture;
+use crate::text::{WriteString, WriteText};
+use crate::write_special::{
+    MemoryConfiguration, RunTimeTable, SerialErrorStatus, SetDayOfWeek, SetRunTimeTable, SetTime,
+    SetTimeFormat, ToggleSpeaker, WriteSpecial,
+};
+use crate::{Command, Packet};
+
+/// What a sign sent back in answer to a read, decoded into structured fields.
+///
+/// Signs answer reads with the same commands used to write, e.g. [`ReadSpecial::Time`](crate::read_special::ReadSpecial)
+/// is answered with a [`SetTime`]. This saves callers matching on those commands and pulling the fields out
+/// themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The contents of a TEXT file, along with its position and transition mode.
+    Text(WriteText),
+    /// The contents of a STRING file.
+    String(WriteString),
+    /// A SMALL DOTS PICTURE file.
+    SmallDotsPicture(WriteSmallDotsPicture),
+    /// The sign's time of day, to the minute.
+    Time(Time),
+    /// Whether the sign's speaker is on.
+    Speaker(bool),
+    /// How the sign's memory is laid out into files. Text and string file sizes are in bytes, and dots picture sizes
+    /// are in dots.
+    MemoryConfiguration(Vec<MemoryConfiguration>),
+    /// The sign's day of the week.
+    DayOfWeek(Weekday),
+    /// Whether the sign shows the time in 24 hour format.
+    TimeFormat { twenty_four_hour: bool },
+    /// When each text file is shown.
+    RunTimeTable(Vec<RunTimeTable>),
+    /// Errors the sign has seen in what was sent to it.
+    SerialErrorStatus(SerialErrorStatus),
+}
+
+impl Response {
+    /// Decodes a command the sign sent in answer to a read.
+    ///
+    /// # Arguments
+    /// * `command`: A command from a packet the sign sent.
+    ///
+    /// # Returns
+    /// The decoded answer, or [`None`] if signs don't answer reads with this command.
+    pub fn classify(command: Command) -> Option<Self> {
+        match command {
+            Command::WriteText(text) => Some(Response::Text(text)),
+            Command::WriteString(string) => Some(Response::String(string)),
+            Command::WriteSmallDotsPicture(dots) => Some(Response::SmallDotsPicture(dots)),
+            Command::WriteSpecial(special) => match special {
+                WriteSpecial::SetTime(SetTime { time }) => Some(Response::Time(time)),
+                WriteSpecial::ToggleSpeaker(ToggleSpeaker { enabled }) => {
+                    Some(Response::Speaker(enabled))
+                }
+                WriteSpecial::ConfigureMemory(configure_memory) => Some(
+                    Response::MemoryConfiguration(configure_memory.configurations().to_vec()),
+                ),
+                WriteSpecial::SetDayOfWeek(SetDayOfWeek { day }) => Some(Response::DayOfWeek(day)),
+                WriteSpecial::SetTimeFormat(SetTimeFormat { twenty_four_hour }) => {
+                    Some(Response::TimeFormat { twenty_four_hour })
+                }
+                WriteSpecial::SetRunTimeTable(SetRunTimeTable { run_time_tables }) => {
+                    Some(Response::RunTimeTable(run_time_tables))
+                }
+                WriteSpecial::SerialErrorStatus(status) => {
+                    Some(Response::SerialErrorStatus(status))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Decodes every answer in a packet the sign sent, leaving out commands that aren't answers to reads.
+    ///
+    /// # Arguments
+    /// * `packet`: The packet the sign sent.
+    ///
+    /// # Returns
+    /// The decoded answers, in order.
+    pub fn from_packet(packet: Packet) -> Vec<Self> {
+        packet
+            .commands
+            .into_iter()
+            .filter_map(Response::classify)
+            .collect()
+    }
+}
@@ -1,9 +1,11 @@
+use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::bytes::complete::take_while;
 use nom::character::complete::anychar;
 use nom::character::complete::char;
 use nom::character::complete::hex_digit0;
 use nom::character::complete::one_of;
+use nom::combinator::map;
 use nom::combinator::map_opt;
 use nom::combinator::map_res;
 use nom::combinator::opt;
@@ -14,7 +16,7 @@ use nom::sequence::preceded;
 use nom::sequence::tuple;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::str;
+use std::{fmt, str, str::FromStr};
 
 use crate::ParseInput;
 use crate::ParseResult;
@@ -67,9 +69,9 @@ pub enum TransitionMode {
     TrumpetAnimation,
     CycleColors,
 }
-impl Into<Vec<u8>> for TransitionMode {
-    fn into(self) -> Vec<u8> {
-        match self {
+impl From<TransitionMode> for Vec<u8> {
+    fn from(val: TransitionMode) -> Self {
+        match val {
             TransitionMode::Rotate => vec![0x61],
             TransitionMode::Hold => vec![0x62],
             TransitionMode::Flash => vec![0x63],
@@ -155,6 +157,93 @@ impl From<Vec<u8>> for TransitionMode {
     }
 }
 
+/// A name that doesn't match any [`TextPosition`], [`TransitionMode`], [`DateFormat`] or [`crate::ProtocolVersion`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownName {}
+
+impl fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown name")
+    }
+}
+
+impl std::error::Error for UnknownName {}
+
+impl TextPosition {
+    /// Names of each position, as used by [`FromStr`].
+    pub const NAMES: [(&'static str, TextPosition); 6] = [
+        ("middle_line", TextPosition::MiddleLine),
+        ("top_line", TextPosition::TopLine),
+        ("bottom_line", TextPosition::BottomLine),
+        ("fill", TextPosition::Fill),
+        ("left", TextPosition::Left),
+        ("right", TextPosition::Right),
+    ];
+}
+
+impl FromStr for TextPosition {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, position)| *position)
+            .ok_or(UnknownName {})
+    }
+}
+
+impl TransitionMode {
+    /// Names of each mode, as used by [`FromStr`].
+    pub const NAMES: [(&'static str, TransitionMode); 33] = [
+        ("rotate", TransitionMode::Rotate),
+        ("hold", TransitionMode::Hold),
+        ("flash", TransitionMode::Flash),
+        ("roll_up", TransitionMode::RollUp),
+        ("roll_down", TransitionMode::RollDown),
+        ("roll_left", TransitionMode::RollLeft),
+        ("roll_right", TransitionMode::RollRight),
+        ("wipe_up", TransitionMode::WipeUp),
+        ("wipe_down", TransitionMode::WipeDown),
+        ("wipe_left", TransitionMode::WipeLeft),
+        ("wipe_right", TransitionMode::WipeRight),
+        ("scroll", TransitionMode::Scroll),
+        ("auto", TransitionMode::AutoMode),
+        ("roll_in", TransitionMode::RollIn),
+        ("roll_out", TransitionMode::RollOut),
+        ("wipe_in", TransitionMode::WipeIn),
+        ("wipe_out", TransitionMode::WipeOut),
+        ("compressed_rotate", TransitionMode::CompressedRotate),
+        ("explode", TransitionMode::Explode),
+        ("clock", TransitionMode::Clock),
+        ("twinkle", TransitionMode::Twinkle),
+        ("sparkle", TransitionMode::Sparkle),
+        ("snow", TransitionMode::Snow),
+        ("interlock", TransitionMode::Interlock),
+        ("switch", TransitionMode::Switch),
+        ("slide", TransitionMode::Slide),
+        ("spray", TransitionMode::Spray),
+        ("starburst", TransitionMode::Starburst),
+        ("welcome", TransitionMode::Welcome),
+        ("slot_machine", TransitionMode::SlotMachine),
+        ("news_flash", TransitionMode::NewsFlash),
+        ("trumpet_animation", TransitionMode::TrumpetAnimation),
+        ("cycle_colors", TransitionMode::CycleColors),
+    ];
+}
+
+impl FromStr for TransitionMode {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, mode)| *mode)
+            .ok_or(UnknownName {})
+    }
+}
+
 impl TextPosition {
     pub fn parse(input: ParseInput) -> ParseResult<Self> {
         map_opt(one_of([0x20, 0x22, 0x26, 0x30, 0x31, 0x32]), |x| {
@@ -163,19 +252,167 @@ impl TextPosition {
     }
 }
 impl TransitionMode {
+    /// Whether this is one of the special modes that not every sign has.
+    pub fn is_special(&self) -> bool {
+        matches!(
+            self,
+            TransitionMode::Twinkle
+                | TransitionMode::Sparkle
+                | TransitionMode::Snow
+                | TransitionMode::Interlock
+                | TransitionMode::Switch
+                | TransitionMode::Slide
+                | TransitionMode::Spray
+                | TransitionMode::Starburst
+                | TransitionMode::Welcome
+                | TransitionMode::SlotMachine
+                | TransitionMode::NewsFlash
+                | TransitionMode::TrumpetAnimation
+                | TransitionMode::CycleColors
+        )
+    }
+
     pub fn parse(input: ParseInput) -> ParseResult<Self> {
-        let (remain, parse) = pair(anychar, opt(anychar))(input)?;
+        // Special modes are 'n' followed by a second character, every other mode is a single character. Anything after
+        // that is the start of the message.
+        let (remain, code) = alt((
+            map(pair(char('n'), anychar), |(n, special)| {
+                vec![n as u8, special as u8]
+            }),
+            map(anychar, |mode| vec![mode as u8]),
+        ))(input)?;
 
-        let mut code: Vec<u8> = vec![parse.0 as u8];
-        if let Some(second) = parse.1 {
-            code.push(second as u8)
-        }
         Ok((remain, TransitionMode::from(code)))
     }
 }
 
+/// Formats the sign can show the date in, for [`call_date`].
+#[repr(u8)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DateFormat {
+    /// MM/DD/YY
+    MonthDayYearSlash = 0x30,
+    /// DD/MM/YY
+    DayMonthYearSlash = 0x31,
+    /// MM-DD-YY
+    MonthDayYearDash = 0x32,
+    /// DD-MM-YY
+    DayMonthYearDash = 0x33,
+    /// MM.DD.YY
+    MonthDayYearDot = 0x34,
+    /// DD.MM.YY
+    DayMonthYearDot = 0x35,
+    /// MM DD YY
+    MonthDayYearSpace = 0x36,
+    /// DD MM YY
+    DayMonthYearSpace = 0x37,
+    /// MMM.DD, YYYY, e.g. JAN.01, 2024
+    MonthNameDayYear = 0x38,
+    /// Day of the week, e.g. MONDAY
+    DayOfWeek = 0x39,
+}
+
+impl DateFormat {
+    /// Names of each format, as used by [`FromStr`].
+    pub const NAMES: [(&'static str, DateFormat); 10] = [
+        ("mm/dd/yy", DateFormat::MonthDayYearSlash),
+        ("dd/mm/yy", DateFormat::DayMonthYearSlash),
+        ("mm-dd-yy", DateFormat::MonthDayYearDash),
+        ("dd-mm-yy", DateFormat::DayMonthYearDash),
+        ("mm.dd.yy", DateFormat::MonthDayYearDot),
+        ("dd.mm.yy", DateFormat::DayMonthYearDot),
+        ("mm dd yy", DateFormat::MonthDayYearSpace),
+        ("dd mm yy", DateFormat::DayMonthYearSpace),
+        ("mmm.dd, yyyy", DateFormat::MonthNameDayYear),
+        ("day_of_week", DateFormat::DayOfWeek),
+    ];
+}
+
+impl FromStr for DateFormat {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, format)| *format)
+            .ok_or(UnknownName {})
+    }
+}
+
+/// Control codes that show the sign's current date when put in a message.
+///
+/// # Arguments
+/// * `format`: Format to show the date in.
+///
+/// # Returns
+/// The control codes, to be inserted into a [`WriteText`] message.
+pub fn call_date(format: DateFormat) -> String {
+    format!("\x0B{}", format as u8 as char)
+}
+
+/// Control code that shows the sign's current time when put in a message.
+///
+/// # Returns
+/// The control code, to be inserted into a [`WriteText`] message.
+pub fn call_time() -> String {
+    "\x13".to_string()
+}
+
+/// Control codes that show the contents of a STRING file when put in a message.
+///
+/// # Arguments
+/// * `label`: Label of the STRING file.
+///
+/// # Returns
+/// The control codes, to be inserted into a [`WriteText`] message.
+pub fn call_string(label: char) -> String {
+    format!("\x10{label}")
+}
+
+/// Control codes that show a SMALL DOTS PICTURE file when put in a message.
+///
+/// # Arguments
+/// * `label`: Label of the picture file.
+///
+/// # Returns
+/// The control codes, to be inserted into a [`WriteText`] message.
+pub fn call_dots(label: char) -> String {
+    format!("\x14{label}")
+}
+
+/// Writes a message to `f`, showing control codes as `<NAME>` (or `<0xNN>` for ones without a name) so they are
+/// readable in logs.
+fn fmt_message(message: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for c in message.chars() {
+        let name = match c {
+            '\x0B' => "CALL_DATE",
+            '\x0C' => "NEW_PAGE",
+            '\x0D' => "NEW_LINE",
+            '\x10' => "CALL_STRING",
+            '\x13' => "CALL_TIME",
+            '\x14' => "CALL_DOTS",
+            '\x1A' => "FONT",
+            '\x1B' => "MODE",
+            '\x1C' => "COLOR",
+            '\x1D' => "CHAR_ATTR",
+            '\x1E' => "SPACING",
+            c if c.is_ascii_control() => {
+                write!(f, "<0x{:02X}>", c as u8)?;
+                continue;
+            }
+            c => {
+                write!(f, "{c}")?;
+                continue;
+            }
+        };
+        write!(f, "<{name}>")?;
+    }
+    Ok(())
+}
+
 // parses any number of ASCII printable characters
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct WriteText {
     pub label: char,
     pub message: String,
@@ -214,7 +451,7 @@ impl WriteText {
             res.push(self.position as u8);
             res.append(&mut self.mode.into());
         }
-        res.extend_from_slice(self.message.as_bytes().into());
+        res.extend_from_slice(self.message.as_bytes());
         res
     }
 
@@ -242,7 +479,19 @@ impl WriteText {
         Ok((remain, w))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+impl fmt::Display for WriteText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WriteText {} {:?} {:?} \"",
+            self.label, self.position, self.mode
+        )?;
+        fmt_message(&self.message, f)?;
+        write!(f, "\"")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ReadText {
     pub label: char,
 }
@@ -267,3 +516,86 @@ impl ReadText {
         Ok((remain, ReadText::new(parse)))
     }
 }
+
+impl fmt::Display for ReadText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReadText {}", self.label)
+    }
+}
+
+/// Writes to a STRING file. Strings shown in a TEXT file with [`call_string`] can be changed without the sign blanking
+/// the message, so they suit values that update often, like counters and sensor readings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteString {
+    pub label: char,
+    pub data: String,
+}
+
+impl WriteString {
+    const COMMANDCODE: u8 = 0x47;
+
+    pub fn new(label: char, data: String) -> Self {
+        Self { label, data }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = vec![Self::COMMANDCODE, self.label as u8];
+        res.extend_from_slice(self.data.as_bytes());
+        res
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, (label, data)) = delimited(
+            tag([0x02, Self::COMMANDCODE]),
+            pair(
+                anychar,                                            // label
+                map_res(take_while(|x| x >= 0x20), str::from_utf8), // string data
+            ),
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // checksum, parsed but discarded
+        )(input)?;
+
+        Ok((remain, WriteString::new(label, data.to_string())))
+    }
+}
+
+impl fmt::Display for WriteString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WriteString {} \"", self.label)?;
+        fmt_message(&self.data, f)?;
+        write!(f, "\"")
+    }
+}
+
+/// Asks the sign for the contents of a STRING file. The sign answers with a [`WriteString`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadString {
+    pub label: char,
+}
+
+impl ReadString {
+    const COMMANDCODE: u8 = 0x48;
+
+    pub fn new(label: char) -> Self {
+        Self { label }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        vec![Self::COMMANDCODE, self.label as u8]
+    }
+
+    pub fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, label) = delimited(
+            tag([0x02, Self::COMMANDCODE]),
+            anychar,                                                // label
+            opt(preceded(char(0x03.into()), count(hex_digit0, 4))), // optional checksum, discarded
+        )(input)?;
+
+        Ok((remain, ReadString::new(label)))
+    }
+}
+
+impl fmt::Display for ReadString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReadString {}", self.label)
+    }
+}
@@ -1,43 +1,64 @@
+use bitflags::bitflags;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::char;
 use nom::character::complete::hex_digit0;
 use nom::character::complete::one_of;
+use nom::character::complete::satisfy;
+use nom::combinator::fail;
 use nom::combinator::map;
 use nom::combinator::map_res;
 use nom::combinator::opt;
 use nom::combinator::value;
+use nom::combinator::verify;
 use nom::multi::count;
+use nom::multi::many1;
+use nom::number::complete::u8;
 use nom::sequence::delimited;
 use nom::sequence::pair;
 use nom::sequence::preceded;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
+use time::Date;
+use time::Month;
 use time::Time;
 
 use crate::ParseInput;
 use crate::ParseResult;
+use crate::ProtocolVersion;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum WriteSpecial {
     SetTime(SetTime),
     ToggleSpeaker(ToggleSpeaker),
     ConfigureMemory(ConfigureMemory),
     ClearMemoryAndFlash(ClearMemoryAndFlash),
     SetDayOfWeek(SetDayOfWeek),
+    SetDate(SetDate),
     SetTimeFormat(SetTimeFormat),
     GenerateSpeakerTone(GenerateSpeakerTone),
     SetRunTimeTable(SetRunTimeTable),
     DisplayAtXYPosition(),
     SoftReset(SoftReset),
     SetRunSequence(SetRunSequence),
-    SetDimminRegister(),
-    SetDimmingTimes(),
+    SetDimmingRegister(SetDimmingRegister),
     SetRunDayTable(SetRunDayTable),
     ClearSerialErrorStatusRegister(ClearSerialErrorStatusRegister),
+    /// Sent by the sign in answer to [`ReadSpecial::SerialErrorStatus`](crate::read_special::ReadSpecial).
+    SerialErrorStatus(SerialErrorStatus),
 }
 
 impl WriteSpecial {
     const COMMANDCODE: u8 = 0x45;
 
+    /// The oldest version of the protocol that has this function.
+    pub fn min_version(&self) -> ProtocolVersion {
+        match self {
+            WriteSpecial::SetDimmingRegister(_) => ProtocolVersion::Alpha2,
+            _ => ProtocolVersion::Alpha1,
+        }
+    }
+
     pub fn encode(&self) -> Vec<u8> {
         let mut res = vec![Self::COMMANDCODE];
         let mut inner = match &self {
@@ -48,6 +69,7 @@ impl WriteSpecial {
                 clear_memory_and_flash.encode()
             }
             WriteSpecial::SetDayOfWeek(set_day_of_week) => set_day_of_week.encode(),
+            WriteSpecial::SetDate(set_date) => set_date.encode(),
             WriteSpecial::SetTimeFormat(set_time_format) => set_time_format.encode(),
             WriteSpecial::GenerateSpeakerTone(generate_speaker_tone) => {
                 generate_speaker_tone.encode()
@@ -56,48 +78,52 @@ impl WriteSpecial {
             WriteSpecial::DisplayAtXYPosition() => todo!(),
             WriteSpecial::SoftReset(soft_reset) => soft_reset.encode(),
             WriteSpecial::SetRunSequence(set_run_sequence) => set_run_sequence.encode(),
-            WriteSpecial::SetDimminRegister() => todo!(),
-            WriteSpecial::SetDimmingTimes() => todo!(),
+            WriteSpecial::SetDimmingRegister(set_dimming_register) => set_dimming_register.encode(),
             WriteSpecial::SetRunDayTable(set_run_day_table) => set_run_day_table.encode(),
             WriteSpecial::ClearSerialErrorStatusRegister(clear_serial_status_register) => {
                 clear_serial_status_register.encode()
             }
+            WriteSpecial::SerialErrorStatus(serial_error_status) => serial_error_status.encode(),
         };
         res.append(&mut inner);
         res
     }
 
     pub fn parse(input: ParseInput) -> ParseResult<Self> {
-        Ok(delimited(
+        delimited(
             tag([0x02, Self::COMMANDCODE]),
             alt((
-                map(SetTime::parse, |x| WriteSpecial::SetTime(x)),
-                map(ToggleSpeaker::parse, |x| WriteSpecial::ToggleSpeaker(x)),
-                map(ConfigureMemory::parse, |x| WriteSpecial::ConfigureMemory(x)),
-                map(ClearMemoryAndFlash::parse, |x| {
-                    WriteSpecial::ClearMemoryAndFlash(x)
-                }),
-                map(SetDayOfWeek::parse, |x| WriteSpecial::SetDayOfWeek(x)),
-                map(SetTimeFormat::parse, |x| WriteSpecial::SetTimeFormat(x)),
-                map(GenerateSpeakerTone::parse, |x| {
-                    WriteSpecial::GenerateSpeakerTone(x)
-                }),
-                map(SetRunTimeTable::parse, |x| WriteSpecial::SetRunTimeTable(x)),
+                map(SetTime::parse, WriteSpecial::SetTime),
+                map(ToggleSpeaker::parse, WriteSpecial::ToggleSpeaker),
+                map(ConfigureMemory::parse, WriteSpecial::ConfigureMemory),
+                map(
+                    ClearMemoryAndFlash::parse,
+                    WriteSpecial::ClearMemoryAndFlash,
+                ),
+                map(SetDayOfWeek::parse, WriteSpecial::SetDayOfWeek),
+                map(SetDate::parse, WriteSpecial::SetDate),
+                map(SetTimeFormat::parse, WriteSpecial::SetTimeFormat),
+                map(
+                    GenerateSpeakerTone::parse,
+                    WriteSpecial::GenerateSpeakerTone,
+                ),
+                map(SetRunTimeTable::parse, WriteSpecial::SetRunTimeTable),
                 // TODO displayatXY position
-                map(SoftReset::parse, |x| WriteSpecial::SoftReset(x)),
-                map(SetRunSequence::parse, |x| WriteSpecial::SetRunSequence(x)),
-                // TODO setDimmingRegister
-                // TODO set dimming times
-                map(SetRunDayTable::parse, |x| WriteSpecial::SetRunDayTable(x)),
-                map(ClearSerialErrorStatusRegister::parse, |x| {
-                    WriteSpecial::ClearSerialErrorStatusRegister(x)
-                }),
+                map(SoftReset::parse, WriteSpecial::SoftReset),
+                map(SetRunSequence::parse, WriteSpecial::SetRunSequence),
+                map(SetDimmingRegister::parse, WriteSpecial::SetDimmingRegister),
+                map(SetRunDayTable::parse, WriteSpecial::SetRunDayTable),
+                map(
+                    ClearSerialErrorStatusRegister::parse,
+                    WriteSpecial::ClearSerialErrorStatusRegister,
+                ),
+                map(SerialErrorStatus::parse, WriteSpecial::SerialErrorStatus),
             )),
             opt(preceded(char(0x03.into()), count(hex_digit0, 4))),
-        )(input)?)
+        )(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetTime {
     pub time: Time,
 }
@@ -119,25 +145,26 @@ impl SetTime {
     }
 
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        let (remain, parse) = preceded(
+        let (remain, time) = preceded(
             char(0x20.into()),
-            pair(
-                map_res(count(one_of("0123456789"), 2), |x| {
-                    x.iter().collect::<String>().parse::<u8>()
-                }),
-                map_res(count(one_of("0123456789"), 2), |x| {
-                    x.iter().collect::<String>().parse::<u8>()
-                }),
+            // A garbled answer from the sign can hold a time that doesn't exist, e.g. 2599
+            map_res(
+                pair(
+                    map_res(count(one_of("0123456789"), 2), |x| {
+                        x.iter().collect::<String>().parse::<u8>()
+                    }),
+                    map_res(count(one_of("0123456789"), 2), |x| {
+                        x.iter().collect::<String>().parse::<u8>()
+                    }),
+                ),
+                |(hours, minutes)| Time::from_hms(hours, minutes, 0),
             ),
         )(input)?;
 
-        Ok((
-            remain,
-            SetTime::new(Time::from_hms(parse.0, parse.1, 0).unwrap()),
-        ))
+        Ok((remain, SetTime::new(time)))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ToggleSpeaker {
     pub enabled: bool,
 }
@@ -172,13 +199,13 @@ impl ToggleSpeaker {
         Ok((remain, ToggleSpeaker::new(parse)))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorStatus {
     Monochrome,
     Tricolor,
     Octocolor,
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StartStopTime {
     time: Time,
 }
@@ -192,11 +219,34 @@ impl StartStopTime {
     pub fn time(&self) -> Time {
         self.time
     }
+
+    /// Encodes the time as two hex digits counting the ten minute periods since midnight.
+    fn encode(&self) -> Vec<u8> {
+        let periods = self.time.hour() * 6 + self.time.minute() / 10;
+        format!("{periods:02X}").into_bytes()
+    }
+
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        map_res(hex_byte, |periods| {
+            StartStopTime::new(periods / 6, periods % 6)
+        })(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+/// Parses two ASCII hex digits.
+fn hex_byte(input: ParseInput) -> ParseResult<u8> {
+    map_res(count(one_of("0123456789ABCDEF"), 2), |x| {
+        u8::from_str_radix(&x.iter().collect::<String>(), 16)
+    })(input)
+}
+
+/// Parses four ASCII hex digits.
+fn hex_u16(input: ParseInput) -> ParseResult<u16> {
+    map_res(count(one_of("0123456789ABCDEF"), 4), |x| {
+        u16::from_str_radix(&x.iter().collect::<String>(), 16)
+    })(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OnPeriod {
     Always,
     Never,
@@ -216,18 +266,31 @@ impl OnPeriod {
             OnPeriod::Range {
                 start_time,
                 end_time,
-            } => [
-                start_time.time.hour() * 6 + start_time.time.minute() / 10,
-                end_time.time.hour() * 6 + end_time.time.minute() / 10,
-            ],
+            } => {
+                let mut res = start_time.encode();
+                res.append(&mut end_time.encode());
+                return res;
+            }
         };
-        format!("{start:0<2X}{end:0<2X}", start = res[0], end = res[1]).into_bytes()
+        format!("{start:02X}{end:02X}", start = res[0], end = res[1]).into_bytes()
     }
+
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        alt((
+            map(pair(tag("FF"), hex_byte), |_| OnPeriod::Always),
+            map(pair(tag("FE"), hex_byte), |_| OnPeriod::Never),
+            map(pair(tag("FD"), hex_byte), |_| OnPeriod::AllDay),
+            map(
+                pair(StartStopTime::parse, StartStopTime::parse),
+                |(start_time, end_time)| OnPeriod::Range {
+                    start_time,
+                    end_time,
+                },
+            ),
+        ))(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FileType {
     Text {
         size: u16,
@@ -242,7 +305,7 @@ pub enum FileType {
         color_status: ColorStatus,
     },
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MemoryConfiguration {
     pub label: char,
     pub file_type: FileType,
@@ -273,9 +336,9 @@ impl MemoryConfiguration {
         }
         let mut file_size = match &self.file_type {
             FileType::Text { size, .. } | FileType::String { size, .. } => {
-                format!("{size:0>4}").into_bytes()
+                format!("{size:04X}").into_bytes()
             }
-            FileType::Dots { x, y, .. } => format!("{y:0>2}{x:0>2}").into_bytes(),
+            FileType::Dots { x, y, .. } => format!("{y:02X}{x:02X}").into_bytes(),
         };
         res.append(&mut file_size);
         let mut file_config: Vec<u8> = match &self.file_type {
@@ -290,14 +353,50 @@ impl MemoryConfiguration {
         res.append(&mut file_config);
         res
     }
+
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        let (remain, (label, file_type, keyboard_accessible)) = tuple((
+            // Labels are printable, which stops the end of the packet being mistaken for another file
+            satisfy(|c| (' '..='~').contains(&c)),
+            one_of("ABCD"),
+            alt((value(true, char('U')), value(false, char('L')))),
+        ))(input)?;
+
+        // The size and configuration that follow depend on the file type
+        let (remain, file_type) = match file_type {
+            'A' => map(pair(hex_u16, OnPeriod::parse), |(size, on_period)| {
+                FileType::Text { size, on_period }
+            })(remain)?,
+            'B' => map(terminated(hex_u16, tag("0000")), |size| FileType::String {
+                size,
+            })(remain)?,
+            _ => map(
+                tuple((
+                    hex_byte,
+                    hex_byte,
+                    terminated(
+                        alt((
+                            value(ColorStatus::Monochrome, char('1')),
+                            value(ColorStatus::Tricolor, char('2')),
+                            value(ColorStatus::Octocolor, char('8')),
+                        )),
+                        tag("000"),
+                    ),
+                )),
+                |(y, x, color_status)| FileType::Dots { x, y, color_status },
+            )(remain)?,
+        };
+
+        Ok((
+            remain,
+            MemoryConfiguration::new(label, file_type, keyboard_accessible),
+        ))
     }
 }
 
 pub struct SignOutOfMemory {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConfigureMemory {
     //TODO check only the last file can have a size of 0
     configurations: Vec<MemoryConfiguration>,
@@ -310,10 +409,8 @@ impl ConfigureMemory {
         for configuration in configurations.iter().rev().skip(1) {
             //TODO ignore for last element
             match configuration.file_type {
-                FileType::Text { size, .. } | FileType::String { size } => {
-                    if size == 0 {
-                        return Err(SignOutOfMemory {});
-                    }
+                FileType::Text { size, .. } | FileType::String { size } if size == 0 => {
+                    return Err(SignOutOfMemory {});
                 }
                 _ => (),
             }
@@ -321,6 +418,11 @@ impl ConfigureMemory {
         Ok(Self { configurations })
     }
 
+    /// The files memory is laid out as, in order.
+    pub fn configurations(&self) -> &[MemoryConfiguration] {
+        &self.configurations
+    }
+
     fn encode(&self) -> Vec<u8> {
         let mut res: Vec<u8> = Self::SPECIAL_LABEL.into();
         for configuration in &self.configurations {
@@ -329,10 +431,14 @@ impl ConfigureMemory {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        // Not checked with new(), a sign's answer to a read describes its memory as it is
+        let (remain, configurations) =
+            preceded(char(0x24.into()), many1(MemoryConfiguration::parse))(input)?;
+
+        Ok((remain, ConfigureMemory { configurations }))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ClearMemoryAndFlash {}
 
 impl ClearMemoryAndFlash {
@@ -346,10 +452,17 @@ impl ClearMemoryAndFlash {
         Self::SPECIAL_LABEL.into()
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        //TODO not parsed yet, fail so the other special functions are still tried
+        fail(input)
+    }
+}
+
+impl Default for ClearMemoryAndFlash {
+    fn default() -> Self {
+        Self::new()
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetDayOfWeek {
     pub day: time::Weekday,
 }
@@ -376,10 +489,69 @@ impl SetDayOfWeek {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        let (remain, day) = preceded(
+            char(0x26.into()),
+            alt((
+                value(time::Weekday::Sunday, char('1')),
+                value(time::Weekday::Monday, char('2')),
+                value(time::Weekday::Tuesday, char('3')),
+                value(time::Weekday::Wednesday, char('4')),
+                value(time::Weekday::Thursday, char('5')),
+                value(time::Weekday::Friday, char('6')),
+                value(time::Weekday::Saturday, char('7')),
+            )),
+        )(input)?;
+
+        Ok((remain, SetDayOfWeek::new(day)))
+    }
+}
+/// Sets the sign's date. The sign shows it in text with [`call_date`](crate::text::call_date), in whichever
+/// [`DateFormat`](crate::text::DateFormat) is picked there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetDate {
+    pub date: Date,
+}
+
+impl SetDate {
+    const SPECIAL_LABEL: &'static [u8] = &[0x3B];
+
+    pub fn new(date: Date) -> Self {
+        Self { date }
+    }
+
+    /// Encodes the date as MMDDYY. The sign only takes two digits of the year, so dates are assumed to be from 2000
+    /// on.
+    fn encode(&self) -> Vec<u8> {
+        let month = self.date.month() as u8;
+        let day = self.date.day();
+        let year = self.date.year().rem_euclid(100);
+        let mut date = format!("{month:0>2}{day:0>2}{year:0>2}").into_bytes();
+        let mut res: Vec<u8> = Self::SPECIAL_LABEL.into();
+        res.append(&mut date);
+        res
+    }
+
+    fn parse(input: ParseInput) -> ParseResult<Self> {
+        let two_digits = || {
+            map_res(count(one_of("0123456789"), 2), |x| {
+                x.iter().collect::<String>().parse::<u8>()
+            })
+        };
+        let (remain, date) = preceded(
+            char(0x3B.into()),
+            map_res(
+                tuple((two_digits(), two_digits(), two_digits())),
+                |(month, day, year)| {
+                    let month = Month::try_from(month)?;
+                    Date::from_calendar_date(2000 + i32::from(year), month, day)
+                },
+            ),
+        )(input)?;
+
+        Ok((remain, SetDate::new(date)))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetTimeFormat {
     pub twenty_four_hour: bool,
 }
@@ -402,17 +574,22 @@ impl SetTimeFormat {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        let (remain, twenty_four_hour) = preceded(
+            char(0x27.into()),
+            alt((value(true, char('M')), value(false, char('S')))),
+        )(input)?;
+
+        Ok((remain, SetTimeFormat::new(twenty_four_hour)))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ToneError {
     DurationOutOfRange,
     RepeatsOutOfRange,
     FrequencyOutOfRange,
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProgrammmableTone {
     frequency: u8,
     duration: u8,
@@ -452,7 +629,7 @@ impl ProgrammmableTone {
         let mut res: Vec<u8> = vec![0x32];
         res.append(
             &mut format!(
-                "{frequency:0<2X}{duration:X}{repeats:X}",
+                "{frequency:02X}{duration:X}{repeats:X}",
                 frequency = self.frequency,
                 duration = self.duration,
                 repeats = self.repeats
@@ -462,10 +639,16 @@ impl ProgrammmableTone {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        preceded(
+            char(0x32.into()),
+            map_res(
+                tuple((hex_byte, hex_digit, hex_digit)),
+                |(frequency, duration, repeats)| Self::new(frequency, duration, repeats),
+            ),
+        )(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ToneType {
     SpeakerOn,
     SpeakerOff,
@@ -477,7 +660,7 @@ pub enum ToneType {
     StoreProgrammableSound,
     TriggerProgrammableSound,
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GenerateSpeakerTone {
     pub tone_type: ToneType,
 }
@@ -505,11 +688,25 @@ impl GenerateSpeakerTone {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        //TODO parse stored and triggered programmable sounds once they can be encoded
+        let (remain, tone_type) = preceded(
+            tag(Self::SPECIAL_LABEL),
+            alt((
+                value(ToneType::SpeakerOn, char(0x41.into())),
+                value(ToneType::SpeakerOff, char(0x42.into())),
+                value(ToneType::Continuous2Seconds, char(0x30.into())),
+                value(ToneType::ShortBeep2Seconds, char(0x31.into())),
+                map(ProgrammmableTone::parse, |programmable_tone| {
+                    ToneType::ProgrammmableTone { programmable_tone }
+                }),
+            )),
+        )(input)?;
+
+        Ok((remain, GenerateSpeakerTone::new(tone_type)))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RunTimeTable {
     label: char,
     on_period: OnPeriod,
@@ -526,11 +723,15 @@ impl RunTimeTable {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        // Labels are printable, which stops the end of the packet being mistaken for another table
+        let (remain, (label, on_period)) =
+            pair(satisfy(|c| (' '..='~').contains(&c)), OnPeriod::parse)(input)?;
+
+        Ok((remain, RunTimeTable::new(label, on_period)))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetRunTimeTable {
     pub run_time_tables: Vec<RunTimeTable>,
 }
@@ -550,11 +751,14 @@ impl SetRunTimeTable {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        let (remain, run_time_tables) =
+            preceded(char(0x29.into()), many1(RunTimeTable::parse))(input)?;
+
+        Ok((remain, SetRunTimeTable::new(run_time_tables)))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SoftReset {}
 
 impl SoftReset {
@@ -569,19 +773,26 @@ impl SoftReset {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        //TODO not parsed yet, fail so the other special functions are still tried
+        fail(input)
+    }
+}
+
+impl Default for SoftReset {
+    fn default() -> Self {
+        Self::new()
     }
 }
 pub struct TooManyTextFiles {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RunSequenceType {
     FollowFileTimes,
     IgnoreFileTimes,
     DeleteAtOffTime,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetRunSequence {
     pub run_seqeunce_type: RunSequenceType,
 
@@ -609,6 +820,11 @@ impl SetRunSequence {
 
     fn encode(&self) -> Vec<u8> {
         let mut res: Vec<u8> = Self::SPECIAL_LABEL.into();
+        res.push(match self.run_seqeunce_type {
+            RunSequenceType::FollowFileTimes => 0x54,
+            RunSequenceType::IgnoreFileTimes => 0x53,
+            RunSequenceType::DeleteAtOffTime => 0x44,
+        });
         if self.keyboard_accessible {
             res.push(0x55)
         } else {
@@ -620,10 +836,11 @@ impl SetRunSequence {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        //TODO not parsed yet, fail so the other special functions are still tried
+        fail(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RunDays {
     Daily,
     WeekDays,
@@ -671,10 +888,42 @@ impl RunDays {
         }
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        alt((
+            value(RunDays::Daily, tag([0x30, 0x30])),
+            value(RunDays::WeekDays, tag([0x38, 0x30])),
+            value(RunDays::Weekends, tag([0x39, 0x30])),
+            value(RunDays::Always, tag([0x41, 0x30])),
+            value(RunDays::Never, tag([0x42, 0x30])),
+            map(pair(weekday, weekday), |(start_day, stop_day)| {
+                RunDays::Range {
+                    start_day,
+                    stop_day,
+                }
+            }),
+        ))(input)
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+
+/// Parses a day of the week, numbered from Sunday as `1`.
+fn weekday(input: ParseInput) -> ParseResult<time::Weekday> {
+    map(one_of("1234567"), |day| match day {
+        '1' => time::Weekday::Sunday,
+        '2' => time::Weekday::Monday,
+        '3' => time::Weekday::Tuesday,
+        '4' => time::Weekday::Wednesday,
+        '5' => time::Weekday::Thursday,
+        '6' => time::Weekday::Friday,
+        _ => time::Weekday::Saturday,
+    })(input)
+}
+
+/// Parses one ASCII hex digit.
+fn hex_digit(input: ParseInput) -> ParseResult<u8> {
+    map(one_of("0123456789ABCDEF"), |digit| {
+        digit.to_digit(16).unwrap_or_default() as u8
+    })(input)
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetRunDayTable {
     pub label: char,
     pub run_days: RunDays,
@@ -694,10 +943,15 @@ impl SetRunDayTable {
         res
     }
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        let (remain, (label, run_days)) = preceded(
+            tag(Self::SPECIAL_LABEL),
+            pair(satisfy(|c| (' '..='~').contains(&c)), RunDays::parse),
+        )(input)?;
+
+        Ok((remain, SetRunDayTable::new(label, run_days)))
     }
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ClearSerialErrorStatusRegister {
     //TODO confirm whether this is correct, the
     //documentation sucks
@@ -716,6 +970,86 @@ impl ClearSerialErrorStatusRegister {
     }
 
     fn parse(input: ParseInput) -> ParseResult<Self> {
-        todo!()
+        //TODO not parsed yet, fail so the other special functions are still tried
+        fail(input)
+    }
+}
+
+impl Default for ClearSerialErrorStatusRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+bitflags! {
+    /// Errors the sign has seen in what was sent to it since the register was last read or cleared. The spec's table
+    /// gives the bits from b5 down to b0.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SerialErrorStatus: u8 {
+        /// A command or file label the sign didn't understand.
+        const ILLEGAL_COMMAND = 0x20;
+        /// A packet's checksum didn't match.
+        const CHECKSUM = 0x10;
+        /// The sign's receive buffer overflowed.
+        const BUFFER_OVERFLOW = 0x08;
+        /// A packet stopped part way through.
+        const TIMEOUT = 0x04;
+        /// A character's stop bit was missing.
+        const FRAMING = 0x02;
+        /// A character had the wrong parity.
+        const PARITY = 0x01;
+    }
+}
+
+impl SerialErrorStatus {
+    const SPECIAL_LABEL: &'static [u8] = &[0x2A];
+    /// Always set in the register, which keeps it a printable character.
+    const ALWAYS_SET: u8 = 0x40;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut res: Vec<u8> = Self::SPECIAL_LABEL.into();
+        res.push(Self::ALWAYS_SET | self.bits());
+        res
+    }
+
+    fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, register) = preceded(
+            char(0x2A.into()),
+            verify(u8, |register| register & 0xC0 == Self::ALWAYS_SET),
+        )(input)?;
+
+        Ok((remain, SerialErrorStatus::from_bits_truncate(register)))
+    }
+}
+
+/// Sets the sign's brightness. Only signs speaking Alpha 2.0 or later have a dimming register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetDimmingRegister {
+    //TODO confirm which end of the range is brightest, the spec only gives the register as two hex digits
+    pub level: u8,
+}
+
+impl SetDimmingRegister {
+    const SPECIAL_LABEL: &'static [u8] = &[0x2F];
+
+    pub fn new(level: u8) -> Self {
+        Self { level }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut res: Vec<u8> = Self::SPECIAL_LABEL.into();
+        res.append(&mut format!("{level:02X}", level = self.level).into_bytes());
+        res
+    }
+
+    fn parse(input: ParseInput) -> ParseResult<Self> {
+        let (remain, level) = preceded(
+            char(0x2F.into()),
+            map_res(count(one_of("0123456789ABCDEF"), 2), |x| {
+                u8::from_str_radix(&x.iter().collect::<String>(), 16)
+            }),
+        )(input)?;
+
+        Ok((remain, SetDimmingRegister::new(level)))
     }
 }
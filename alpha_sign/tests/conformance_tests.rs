@@ -0,0 +1,58 @@
+use alpha_sign::Packet;
+
+/// A packet from `fixtures/packets.txt`.
+struct Fixture {
+    description: String,
+    display: Option<String>,
+    bytes: Vec<u8>,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let lines: Vec<&str> = include_str!("fixtures/packets.txt")
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    lines
+        .chunks(3)
+        .map(|block| {
+            let [description, display, bytes] = block else {
+                panic!("incomplete fixture {block:?}")
+            };
+            Fixture {
+                description: description.to_string(),
+                display: (*display != "-").then(|| display.to_string()),
+                bytes: bytes
+                    .split_whitespace()
+                    .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_fixtures_parse_and_encode() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty());
+
+    for fixture in fixtures {
+        let Ok((remain, pkt)) = Packet::parse(fixture.bytes.as_slice()) else {
+            panic!("{}: failed to parse", fixture.description)
+        };
+        assert!(
+            remain.is_empty(),
+            "{}: bytes left over",
+            fixture.description
+        );
+        if let Some(display) = fixture.display {
+            assert_eq!(pkt.to_string(), display, "{}", fixture.description);
+        }
+        assert_eq!(
+            pkt.encode().unwrap(),
+            fixture.bytes,
+            "{}: encoded differently",
+            fixture.description
+        );
+    }
+}
@@ -1,13 +1,54 @@
+use alpha_sign::dots::Bitmap;
+use alpha_sign::dots::DotColor;
+#[cfg(feature = "experimental")]
+use alpha_sign::dots::ReadLargeDotsPicture;
+use alpha_sign::dots::ReadSmallDotsPicture;
+use alpha_sign::dots::RgbColor;
+#[cfg(feature = "experimental")]
+use alpha_sign::dots::WriteLargeDotsPicture;
+use alpha_sign::dots::WriteRgbDotsPicture;
+use alpha_sign::dots::WriteSmallDotsPicture;
+use alpha_sign::read_special::ReadSpecial;
+use alpha_sign::response::Response;
+use alpha_sign::text::call_date;
+use alpha_sign::text::call_string;
+use alpha_sign::text::DateFormat;
+use alpha_sign::text::ReadString;
 use alpha_sign::text::ReadText;
+use alpha_sign::text::TextPosition;
+use alpha_sign::text::TransitionMode;
+use alpha_sign::text::WriteString;
 use alpha_sign::text::WriteText;
+use alpha_sign::write_special::ColorStatus;
+use alpha_sign::write_special::ConfigureMemory;
+use alpha_sign::write_special::FileType;
+use alpha_sign::write_special::GenerateSpeakerTone;
+use alpha_sign::write_special::MemoryConfiguration;
+use alpha_sign::write_special::OnPeriod;
+use alpha_sign::write_special::ProgrammmableTone;
+use alpha_sign::write_special::RunDays;
+use alpha_sign::write_special::RunSequenceType;
+use alpha_sign::write_special::RunTimeTable;
+use alpha_sign::write_special::SerialErrorStatus;
+use alpha_sign::write_special::SetDate;
+use alpha_sign::write_special::SetDimmingRegister;
+use alpha_sign::write_special::SetRunDayTable;
+use alpha_sign::write_special::SetRunSequence;
+use alpha_sign::write_special::SetRunTimeTable;
 use alpha_sign::write_special::SetTime;
+use alpha_sign::write_special::StartStopTime;
 use alpha_sign::write_special::ToggleSpeaker;
+use alpha_sign::write_special::ToneType;
 use alpha_sign::write_special::WriteSpecial;
 use alpha_sign::Command;
 use alpha_sign::Packet;
+use alpha_sign::ProtocolProfile;
+use alpha_sign::ProtocolVersion;
 use alpha_sign::SignSelector;
-use time;
+use time::Date;
+use time::Month;
 use time::Time;
+use time::Weekday;
 
 #[test]
 fn test_parse_write_text() {
@@ -23,6 +64,32 @@ fn test_parse_write_text() {
     assert_eq!(res, pkt)
 }
 
+#[test]
+fn test_display_packet() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![
+            Command::WriteText(WriteText::new('A', "hi\x1C1there".to_string())),
+            Command::ReadText(ReadText::new('B')),
+        ],
+    );
+
+    assert_eq!(
+        pkt.to_string(),
+        "All:00 | WriteText A MiddleLine AutoMode \"hi<COLOR>1there\" | ReadText B"
+    )
+}
+
+#[test]
+fn test_parse_names() {
+    assert_eq!("top_line".parse(), Ok(TextPosition::TopLine));
+    assert_eq!("roll_up".parse(), Ok(TransitionMode::RollUp));
+    assert!("sideways".parse::<TransitionMode>().is_err());
+    for (name, mode) in TransitionMode::NAMES {
+        assert_eq!(name.parse(), Ok(mode));
+    }
+}
+
 #[test]
 fn test_parse_read_text() {
     let pkt = Packet::new(
@@ -52,6 +119,29 @@ fn test_parse_set_time() {
     assert_eq!(res, pkt)
 }
 
+#[test]
+fn test_parse_set_date() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSpecial(WriteSpecial::SetDate(SetDate::new(
+            Date::from_calendar_date(2026, Month::March, 7).unwrap(),
+        )))],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_set_date_rejects_missing_day() {
+    // 31st of February
+    let pkt = b"\0\0\0\0\0\x01Z00\x02E;023126\x030000\x04";
+    assert!(Packet::parse(pkt).is_err());
+}
+
 #[test]
 fn test_parse_toggle_speaker_on() {
     let pkt = Packet::new(
@@ -160,3 +250,526 @@ fn test_parse_multiple_commands_and_selectors() {
 
     assert_eq!(res, pkt)
 }
+
+#[test]
+fn test_parse_set_dimming_register() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSpecial(WriteSpecial::SetDimmingRegister(
+            SetDimmingRegister::new(0x3C),
+        ))],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_generate_speaker_tone() {
+    let programmable_tone = ProgrammmableTone::new(0x05, 0x3, 0xF).unwrap();
+    let tone =
+        WriteSpecial::GenerateSpeakerTone(GenerateSpeakerTone::new(ToneType::ProgrammmableTone {
+            programmable_tone,
+        }));
+    // The frequency is padded on the left, like the other hex fields
+    assert_eq!(tone.encode(), b"E(2053F");
+
+    for tone in [
+        tone,
+        WriteSpecial::GenerateSpeakerTone(GenerateSpeakerTone::new(ToneType::ShortBeep2Seconds)),
+    ] {
+        let pkt = Packet::new(
+            vec![SignSelector::default()],
+            vec![Command::WriteSpecial(tone)],
+        );
+
+        let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+            panic!()
+        };
+
+        assert_eq!(res, pkt)
+    }
+}
+
+#[test]
+fn test_parse_set_run_day_table() {
+    let range = WriteSpecial::SetRunDayTable(SetRunDayTable::new(
+        'B',
+        RunDays::Range {
+            start_day: Weekday::Monday,
+            stop_day: Weekday::Friday,
+        },
+    ));
+    assert_eq!(range.encode(), b"E2B26");
+
+    for run_day_table in [
+        range,
+        WriteSpecial::SetRunDayTable(SetRunDayTable::new('A', RunDays::Weekends)),
+    ] {
+        let pkt = Packet::new(
+            vec![SignSelector::default()],
+            vec![Command::WriteSpecial(run_day_table)],
+        );
+
+        let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+            panic!()
+        };
+
+        assert_eq!(res, pkt)
+    }
+}
+
+#[test]
+fn test_encode_for_older_protocol() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSpecial(WriteSpecial::SetDimmingRegister(
+            SetDimmingRegister::new(0x3C),
+        ))],
+    );
+
+    assert!(pkt.encode_for(ProtocolVersion::Alpha1).is_err());
+    assert!(pkt.encode_for(ProtocolVersion::Alpha2).is_ok());
+}
+
+#[test]
+fn test_profile_downgrades_special_modes() {
+    let text = WriteText::new('A', "test".to_string()).mode(TransitionMode::Sparkle);
+
+    let (res, changes) =
+        ProtocolProfile::for_version(ProtocolVersion::Alpha1).downgrade(text.clone());
+    assert_eq!(res.mode, TransitionMode::Hold);
+    assert_eq!(changes.len(), 1);
+
+    let (res, changes) =
+        ProtocolProfile::for_version(ProtocolVersion::Alpha3).downgrade(text.clone());
+    assert_eq!(res, text);
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn test_profile_from_memory_configuration() {
+    let text_file = |label, size| {
+        MemoryConfiguration::new(
+            label,
+            FileType::Text {
+                size,
+                on_period: OnPeriod::Always,
+            },
+            false,
+        )
+    };
+    let dots_file = MemoryConfiguration::new(
+        'C',
+        FileType::Dots {
+            x: 80,
+            y: 7,
+            color_status: ColorStatus::Tricolor,
+        },
+        false,
+    );
+
+    let profile = ProtocolProfile::from_memory_configuration(
+        ProtocolVersion::Alpha2,
+        &[text_file('A', 8), text_file('B', 4)],
+    );
+    assert_eq!(profile.version, ProtocolVersion::Alpha2);
+    assert_eq!(profile.max_text_len, Some(8));
+    assert!(!profile.dots_pictures);
+
+    let profile = ProtocolProfile::from_memory_configuration(
+        ProtocolVersion::Alpha2,
+        &[text_file('A', 8), dots_file, text_file('B', 0)],
+    );
+    assert_eq!(profile.max_text_len, None);
+    assert!(profile.dots_pictures);
+}
+
+#[test]
+fn test_profile_cuts_text_short() {
+    let profile = ProtocolProfile {
+        max_text_len: Some(8),
+        ..ProtocolProfile::for_version(ProtocolVersion::Alpha3)
+    };
+
+    let (res, changes) = profile.downgrade(WriteText::new('A', "far too long".to_string()));
+    assert_eq!(res.message, "far too ");
+    assert_eq!(changes.len(), 1);
+
+    let text = WriteText::new('A', "short".to_string());
+    let (res, changes) = profile.downgrade(text.clone());
+    assert_eq!(res, text);
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn test_call_date() {
+    let format: DateFormat = "dd/mm/yy".parse().unwrap();
+    assert_eq!(format, DateFormat::DayMonthYearSlash);
+
+    let text = WriteText::new('A', format!("Today is {}", call_date(format)));
+    assert_eq!(text.encode(), b"AAToday is \x0B1");
+    assert_eq!(
+        text.to_string(),
+        "WriteText A MiddleLine AutoMode \"Today is <CALL_DATE>1\""
+    );
+}
+
+#[test]
+fn test_parse_write_string() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteString(WriteString::new(
+            '1',
+            "42".to_string(),
+        ))],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt);
+    assert_eq!(call_string('1'), "\x101");
+}
+
+#[test]
+fn test_parse_read_string() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::ReadString(ReadString::new('1'))],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt);
+    assert!(res.commands[0].is_read());
+}
+
+#[test]
+fn test_parse_write_small_dots_picture() {
+    let bitmap = Bitmap::new(vec![
+        vec![DotColor::Red, DotColor::Off, DotColor::Green],
+        vec![DotColor::Off, DotColor::Amber, DotColor::Off],
+    ])
+    .unwrap();
+    let write_dots = WriteSmallDotsPicture::new('Q', bitmap).unwrap();
+    assert_eq!(write_dots.encode(), b"IQ0203102\r030\r");
+
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSmallDotsPicture(write_dots)],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_bitmap_rows_must_match() {
+    assert!(Bitmap::new(vec![vec![DotColor::Red], vec![]]).is_err());
+}
+
+#[test]
+fn test_parse_read_small_dots_picture() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::ReadSmallDotsPicture(ReadSmallDotsPicture::new(
+            'Q',
+        ))],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt);
+    assert!(res.commands[0].is_read());
+}
+
+#[test]
+fn test_parse_small_dots_picture_response() {
+    // A sign's reply to a read, addressed back from a response packet
+    let response = b"\x00\x00\x00\x00\x00\x01000\x02IQ010212\x0D\x03\x04";
+    let Ok((_, res)) = Packet::parse(response) else {
+        panic!()
+    };
+
+    let Some(Command::WriteSmallDotsPicture(write_dots)) = res.commands.first() else {
+        panic!()
+    };
+    assert_eq!(write_dots.label, 'Q');
+    assert_eq!(
+        write_dots.bitmap.rows(),
+        [vec![DotColor::Red, DotColor::Green]]
+    );
+}
+
+#[test]
+#[cfg(feature = "experimental")]
+fn test_parse_large_dots_picture() {
+    let bitmap = Bitmap::new(vec![vec![DotColor::Yellow; 300]; 2]).unwrap();
+    assert!(WriteSmallDotsPicture::new('R', bitmap.clone()).is_err());
+
+    let write_dots = WriteLargeDotsPicture::new('R', bitmap).unwrap();
+    assert!(write_dots.encode().starts_with(b"MR0002012C8888"));
+
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![
+            Command::WriteLargeDotsPicture(write_dots),
+            Command::ReadLargeDotsPicture(ReadLargeDotsPicture::new('R')),
+        ],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_write_rgb_dots_picture() {
+    let bitmap = Bitmap::new(vec![
+        vec![RgbColor::new(0xFF, 0x80, 0x00), RgbColor::default()],
+        vec![RgbColor::default(), RgbColor::new(0x12, 0x34, 0xAB)],
+    ])
+    .unwrap();
+    let write_dots = WriteRgbDotsPicture::new('S', bitmap).unwrap();
+    assert_eq!(
+        write_dots.encode(),
+        b"KS00020002FF8000000000\r0000001234AB\r"
+    );
+
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteRgbDotsPicture(write_dots)],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt);
+    assert!(pkt.encode_for(ProtocolVersion::Alpha2).is_err());
+}
+
+#[test]
+fn test_parse_read_special() {
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![
+            Command::ReadSpecial(ReadSpecial::Time),
+            Command::ReadSpecial(ReadSpecial::RunTimeTable),
+        ],
+    );
+    assert!(pkt.commands.iter().all(Command::is_read));
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_time_out_of_range() {
+    // The time of day 25:99, as a garbled answer to ReadSpecial::Time might hold
+    let bytes = b"\0\0\0\0\0\x01Z00\x02E 2599\x04";
+    assert!(Packet::parse(bytes).is_err());
+
+    let bytes = b"\0\0\0\0\0\x01Z00\x02E 1230\x04";
+    assert!(Packet::parse(bytes).is_ok());
+}
+
+#[test]
+fn test_parse_run_time_table_response() {
+    let set_run_time_table = WriteSpecial::SetRunTimeTable(SetRunTimeTable::new(vec![
+        RunTimeTable::new('A', OnPeriod::Always),
+        RunTimeTable::new(
+            'B',
+            OnPeriod::Range {
+                start_time: StartStopTime::new(0, 3).unwrap(),
+                end_time: StartStopTime::new(17, 0).unwrap(),
+            },
+        ),
+    ]));
+    assert_eq!(set_run_time_table.encode(), b"E)AFF00B0366");
+
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSpecial(set_run_time_table)],
+    );
+
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_memory_configuration_response() {
+    let Ok(configure_memory) = ConfigureMemory::new(vec![
+        MemoryConfiguration::new(
+            'A',
+            FileType::Text {
+                size: 256,
+                on_period: OnPeriod::Always,
+            },
+            false,
+        ),
+        MemoryConfiguration::new('B', FileType::String { size: 16 }, true),
+        MemoryConfiguration::new(
+            'C',
+            FileType::Dots {
+                x: 80,
+                y: 7,
+                color_status: ColorStatus::Tricolor,
+            },
+            false,
+        ),
+    ]) else {
+        panic!()
+    };
+    let configure_memory = WriteSpecial::ConfigureMemory(configure_memory);
+    assert_eq!(
+        configure_memory.encode(),
+        b"E$AAL0100FF00BBU00100000CCL07502000"
+    );
+
+    // A sign answers a read of its memory configuration in the same form it is set with
+    let read = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::ReadSpecial(ReadSpecial::MemoryConfiguration)],
+    );
+    let Ok((_, res)) = Packet::parse(read.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+    assert_eq!(res, read);
+
+    let pkt = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::WriteSpecial(configure_memory)],
+    );
+    let Ok((_, res)) = Packet::parse(pkt.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+
+    let Some(Command::WriteSpecial(WriteSpecial::ConfigureMemory(configure_memory))) =
+        res.commands.first()
+    else {
+        panic!()
+    };
+    assert_eq!(configure_memory.configurations().len(), 3);
+    assert_eq!(res, pkt)
+}
+
+#[test]
+fn test_parse_serial_error_status_response() {
+    let read = Packet::new(
+        vec![SignSelector::default()],
+        vec![Command::ReadSpecial(ReadSpecial::SerialErrorStatus)],
+    );
+    let Ok((_, res)) = Packet::parse(read.encode().unwrap().as_slice()) else {
+        panic!()
+    };
+    assert_eq!(res, read);
+
+    // A sign that has seen a bad checksum and a framing error since the register was last read
+    let response = b"\x00\x00\x00\x00\x00\x01000\x02E*R\x03\x04";
+    let Ok((_, res)) = Packet::parse(response) else {
+        panic!()
+    };
+
+    let Some(Command::WriteSpecial(WriteSpecial::SerialErrorStatus(status))) = res.commands.first()
+    else {
+        panic!()
+    };
+    assert_eq!(
+        *status,
+        SerialErrorStatus::CHECKSUM | SerialErrorStatus::FRAMING
+    );
+
+    // Parity is the lowest bit and an illegal command the highest, which a mix-up of the bit order would swap
+    for (register, expected) in [
+        (b'A', SerialErrorStatus::PARITY),
+        (b'`', SerialErrorStatus::ILLEGAL_COMMAND),
+        (b'H', SerialErrorStatus::BUFFER_OVERFLOW),
+    ] {
+        let response = [
+            b"\x00\x00\x00\x00\x00\x01000\x02E*".as_slice(),
+            &[register],
+            b"\x03\x04",
+        ]
+        .concat();
+        let Ok((_, res)) = Packet::parse(&response) else {
+            panic!()
+        };
+        let Some(Command::WriteSpecial(WriteSpecial::SerialErrorStatus(status))) =
+            res.commands.first()
+        else {
+            panic!()
+        };
+        assert_eq!(*status, expected);
+    }
+}
+
+#[test]
+fn test_classify_responses() {
+    // A sign answering reads of its memory configuration, serial error status and a text file in one packet
+    let response = b"\x00\x00\x00\x00\x00\x01000\x02E$AAL0100FF00BBU00100000\x03\x02E*A\x03\x02AA\x1b bHi\x03\x04";
+    let Ok((_, pkt)) = Packet::parse(response) else {
+        panic!()
+    };
+
+    assert_eq!(
+        Response::from_packet(pkt),
+        vec![
+            Response::MemoryConfiguration(vec![
+                MemoryConfiguration::new(
+                    'A',
+                    FileType::Text {
+                        size: 256,
+                        on_period: OnPeriod::Always,
+                    },
+                    false,
+                ),
+                MemoryConfiguration::new('B', FileType::String { size: 16 }, true),
+            ]),
+            Response::SerialErrorStatus(SerialErrorStatus::PARITY),
+            Response::Text(
+                WriteText::new('A', "Hi".to_string())
+                    .position(TextPosition::MiddleLine)
+                    .mode(TransitionMode::Hold)
+            ),
+        ]
+    );
+    assert_eq!(
+        Response::classify(Command::ReadSpecial(ReadSpecial::Time)),
+        None
+    );
+}
+
+#[test]
+fn test_encode_run_sequence() {
+    let Ok(run_sequence) =
+        SetRunSequence::new(RunSequenceType::IgnoreFileTimes, false, vec!['A', 'B'])
+    else {
+        panic!()
+    };
+
+    assert_eq!(
+        WriteSpecial::SetRunSequence(run_sequence).encode(),
+        b"E.SLAB"
+    );
+}
@@ -0,0 +1,352 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// One request to the HTTP API and the response to it, as recorded by [`ApiRecorder`] and replayed by the API
+/// regression tests.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// Method of the request, e.g. `PUT`.
+    pub method: String,
+    /// Path and query of the request.
+    pub uri: String,
+    /// Content type of the request, if it had a body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Whether the request had an `Authorization` header. The header itself isn't recorded, so tokens don't end up in
+    /// fixtures.
+    #[serde(default)]
+    pub authorization: bool,
+    /// Body of the request.
+    pub request: String,
+    /// Status code of the response.
+    pub status: u16,
+    /// Body of the response, in canonical form, see [`canonical`].
+    pub response: String,
+}
+
+/// Records requests to the HTTP API and the responses to them, to use as fixtures for the API regression tests.
+///
+/// Each line of a recording is one [`Exchange`] as JSON.
+#[derive(Clone)]
+pub struct ApiRecorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl ApiRecorder {
+    /// Creates an [`ApiRecorder`] that appends to the given file.
+    ///
+    /// # Arguments
+    /// * `path`: Path of the file to record to. It is created if it doesn't exist.
+    ///
+    /// # Returns
+    /// A new [`ApiRecorder`].
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Records an exchange. Failures are logged rather than returned so a full disk doesn't stop the API working.
+    ///
+    /// # Arguments
+    /// * `exchange`: The exchange.
+    pub fn record(&self, exchange: &Exchange) {
+        let res = serde_json::to_string(exchange)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file.lock().unwrap(), "{line}"));
+        if let Err(e) = res {
+            tracing::warn!("Failed to record API exchange: {e}");
+        }
+    }
+}
+
+/// Middleware recording every request and its response with an [`ApiRecorder`].
+///
+/// Both bodies are read in full before being passed on, so request bodies over `max_body_bytes` are refused with 413
+/// Payload Too Large here rather than further in.
+///
+/// # Arguments
+/// * `recorder`: Where to record to, and the largest request body to read.
+/// * `request`: The request.
+/// * `next`: The rest of the app.
+///
+/// # Returns
+/// The response, unchanged.
+pub async fn record_exchanges(
+    State((recorder, max_body_bytes)): State<(ApiRecorder, usize)>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Some(request_body) = read_body(body, max_body_bytes).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let method = parts.method.to_string();
+    let uri = parts.uri.to_string();
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let authorization = parts.headers.contains_key(header::AUTHORIZATION);
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(request_body.clone())))
+        .await;
+    let (parts, body) = response.into_parts();
+    let Some(response_body) = read_body(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    recorder.record(&Exchange {
+        method,
+        uri,
+        content_type,
+        authorization,
+        request: String::from_utf8_lossy(&request_body).into_owned(),
+        status: parts.status.as_u16(),
+        response: canonical(&response_body),
+    });
+    Response::from_parts(
+        parts,
+        axum::body::boxed(axum::body::Full::from(response_body)),
+    )
+}
+
+/// Reads a body in full.
+///
+/// # Arguments
+/// * `body`: The body.
+/// * `max_bytes`: Most bytes to read.
+///
+/// # Returns
+/// The bytes of the body, or [`None`] if it couldn't be read or was too big.
+pub async fn read_body<B>(mut body: B, max_bytes: usize) -> Option<Bytes>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > max_bytes {
+            return None;
+        }
+    }
+    Some(bytes.into())
+}
+
+/// Puts a response body into a form that can be compared with a recording. JSON is written out again with its keys
+/// sorted, so the order fields are serialized in doesn't matter.
+///
+/// # Arguments
+/// * `body`: The body.
+///
+/// # Returns
+/// The body as text.
+pub fn canonical(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(json) => json.to_string(),
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+/// Reads the exchanges in a recording made by an [`ApiRecorder`].
+///
+/// # Arguments
+/// * `reader`: The recording.
+///
+/// # Returns
+/// The exchanges, in order. Blank lines are skipped.
+#[cfg(test)]
+pub fn read_exchanges(reader: impl io::BufRead) -> io::Result<Vec<Exchange>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alpha_sign::text::WriteText;
+    use axum::{body::Body, http::Request, Router};
+    use tokio::sync::{mpsc, watch};
+    use tower::ServiceExt;
+    use tracing_subscriber::{reload, EnvFilter};
+
+    use super::*;
+    use crate::config::Config;
+    use crate::display::{DisplayBackend, TerminalBackend};
+    use crate::web_server::{
+        self, app, APICommand, APIResponse, AppState, DisplayedText, QueuedCommand,
+    };
+
+    /// The sign the replayed requests are sent to, standing in for the sign loop in `main`.
+    struct FakeSign {
+        display: TerminalBackend,
+        displayed_tx: watch::Sender<Option<DisplayedText>>,
+    }
+
+    impl FakeSign {
+        /// Handles one command the way the sign loop would.
+        fn handle(&mut self, command: APICommand) {
+            match command {
+                APICommand::WriteText(text, _)
+                | APICommand::WriteTextRandomMode(text, _, _)
+                | APICommand::WriteBanner(text, _, _) => self.write(text),
+                APICommand::ReadText(read, tx) => {
+                    let text = self.display.read_text(read).unwrap();
+                    let _ = tx.send(APIResponse::ReadText(text));
+                }
+                APICommand::ConfigureMemory(_, _, tx) | APICommand::WriteSpecial(_, tx) => {
+                    let _ = tx.send(APIResponse::Done);
+                }
+                APICommand::PlayScene(_)
+                | APICommand::NightMode(_, _)
+                | APICommand::ReadTime(_) => {}
+            }
+        }
+
+        fn write(&mut self, text: WriteText) {
+            self.displayed_tx
+                .send_replace(Some(DisplayedText::from(&text)));
+            self.display.write_text(text).unwrap();
+        }
+    }
+
+    /// Makes an app around the config the fixtures were recorded with, along with the sign it sends commands to.
+    fn test_app() -> (Router, mpsc::UnboundedReceiver<QueuedCommand>, FakeSign) {
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (_, config_rx) = watch::channel(Config {
+            admin_token: Some("secret".to_string()),
+            ..Config::default()
+        });
+        let (displayed_tx, displayed_rx) = watch::channel(None);
+        let (_, connected_rx) = watch::channel(true);
+        let (_, clock_drift_rx) = watch::channel(None);
+        let (log_layer, log_filter) = reload::Layer::new(EnvFilter::new("yhs_sign=info"));
+        // The handle only works while its layer is alive
+        Box::leak(Box::new(log_layer));
+        let app = app(AppState::new(
+            command_tx,
+            config_rx,
+            displayed_rx,
+            connected_rx,
+            None,
+            clock_drift_rx,
+            log_filter,
+        ));
+        let sign = FakeSign {
+            display: TerminalBackend::new(),
+            displayed_tx,
+        };
+        (app, command_rx, sign)
+    }
+
+    /// Sends the request of an exchange, handling the commands it sends to the sign while it runs.
+    ///
+    /// # Returns
+    /// The status code and canonical body of the response.
+    async fn replay(
+        app: &Router,
+        command_rx: &mut mpsc::UnboundedReceiver<QueuedCommand>,
+        sign: &mut FakeSign,
+        exchange: &Exchange,
+    ) -> (u16, String) {
+        let mut request = Request::builder()
+            .method(exchange.method.as_str())
+            .uri(&exchange.uri);
+        if let Some(content_type) = &exchange.content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        if exchange.authorization {
+            request = request.header(header::AUTHORIZATION, "Bearer secret");
+        }
+        let request = request.body(Body::from(exchange.request.clone())).unwrap();
+
+        let response = app.clone().oneshot(request);
+        tokio::pin!(response);
+        let response = loop {
+            tokio::select! {
+                response = &mut response => break response.unwrap(),
+                Some(queued) = command_rx.recv() => sign.handle(queued.command),
+            }
+        };
+        // Commands sent without waiting for an answer are handled before the next request, as the sign loop would
+        while let Ok(queued) = command_rx.try_recv() {
+            sign.handle(queued.command);
+        }
+
+        let status = response.status().as_u16();
+        let body = read_body(response.into_body(), usize::MAX).await.unwrap();
+        (status, canonical(&body))
+    }
+
+    #[tokio::test]
+    async fn test_replay_api_fixtures() {
+        let exchanges =
+            read_exchanges(include_str!("../tests/fixtures/api.jsonl").as_bytes()).unwrap();
+        assert!(!exchanges.is_empty());
+        let (app, mut command_rx, mut sign) = test_app();
+        for exchange in &exchanges {
+            assert_eq!(
+                replay(&app, &mut command_rx, &mut sign, exchange).await,
+                (exchange.status, exchange.response.clone()),
+                "{} {}",
+                exchange.method,
+                exchange.uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_exchanges() {
+        let path = std::env::temp_dir().join(format!("yhs-sign-test-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (app, mut command_rx, mut sign) = test_app();
+        let app = web_server::record(app, ApiRecorder::create(&path).unwrap());
+        let exchanges = [
+            Exchange {
+                method: "PUT".to_string(),
+                uri: "/api/v1/text/test".to_string(),
+                content_type: Some("application/json".to_string()),
+                authorization: false,
+                request: r#"{"text": "Recorded"}"#.to_string(),
+                status: 200,
+                response: String::new(),
+            },
+            Exchange {
+                method: "GET".to_string(),
+                uri: "/api/v1/admin/log-level".to_string(),
+                content_type: None,
+                authorization: true,
+                request: String::new(),
+                status: 200,
+                response: r#"{"filter":"yhs_sign=info"}"#.to_string(),
+            },
+        ];
+        for exchange in &exchanges {
+            replay(&app, &mut command_rx, &mut sign, exchange).await;
+        }
+
+        let recording = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read_exchanges(recording.as_bytes()).unwrap(), exchanges);
+        // The token itself is never written out
+        assert!(!recording.contains("secret"));
+    }
+}
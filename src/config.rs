@@ -0,0 +1,419 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use alpha_sign::text::TransitionMode;
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+use time::{OffsetDateTime, Time, UtcOffset};
+
+use crate::{
+    display::{Backend, BannerPicture},
+    duration::{self, HumanDuration},
+};
+
+/// Settings loaded from the config file. These can be changed while the service is running by sending it a SIGHUP.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Keys that text can be PUT to.
+    pub text_keys: Vec<String>,
+    /// Kinds of display text PUT to each key is shown on. Keys without a route use `default_route`.
+    pub routes: HashMap<String, Vec<Backend>>,
+    /// Kinds of display text is shown on when its key has no route. Text is shown everywhere if this isn't set.
+    pub default_route: Option<Vec<Backend>>,
+    /// Text shown on the sign in turn when the service starts, before any text is PUT.
+    pub startup: Vec<StartupStep>,
+    /// Files to lay out the sign's memory as when it is reprovisioned.
+    pub memory: Vec<MemoryFile>,
+    /// Whether the files can be edited with the sign's IR keyboard once it is reprovisioned.
+    pub keyboard_access: bool,
+    /// Bearer token needed for the `/admin` routes. They are refused altogether if this isn't set.
+    pub admin_token: Option<String>,
+    /// Animations that can be played on the sign, by name.
+    pub scenes: HashMap<String, Vec<Frame>>,
+    /// How bright the sign is in and out of night mode.
+    pub night: NightMode,
+    /// How often the sign's clock is checked for drift.
+    pub clock: ClockCheck,
+    /// Keys whose text is shown in big characters.
+    pub banner: Banner,
+    /// Transition modes never picked for text PUT with the `random` mode, by name, e.g. `news_flash`.
+    #[serde(deserialize_with = "deserialize_modes")]
+    pub random_mode_exclusions: Vec<TransitionMode>,
+}
+
+/// Deserializes a list of transition modes from their names.
+fn deserialize_modes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<TransitionMode>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            name.parse()
+                .map_err(|_| serde::de::Error::custom(format!("unknown transition mode `{name}`")))
+        })
+        .collect()
+}
+
+/// Brightness and quiet hours for night mode, when the sign shows just the time.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct NightMode {
+    /// Dimming register value set when night mode starts. Brightness is left alone if not set.
+    pub dimming: Option<u8>,
+    /// Dimming register value set when night mode ends. Brightness is left alone if not set.
+    pub day_dimming: Option<u8>,
+    /// Local time of day, e.g. `22:00`, that night mode starts. There are no quiet hours unless `end` is set too.
+    #[serde(default, deserialize_with = "deserialize_time_of_day")]
+    pub start: Option<Time>,
+    /// Local time of day, e.g. `07:30`, that night mode ends.
+    #[serde(default, deserialize_with = "deserialize_time_of_day")]
+    pub end: Option<Time>,
+    /// Offset from UTC of the local time `start` and `end` are in, e.g. `+01:00`. UTC is used if this isn't set. It
+    /// doesn't follow daylight saving, so needs changing when the clocks do.
+    #[serde(default, deserialize_with = "deserialize_utc_offset")]
+    pub utc_offset: Option<UtcOffset>,
+}
+
+impl NightMode {
+    /// Looks up the dimming register value to set when night mode starts or ends.
+    ///
+    /// # Arguments
+    /// * `enabled`: Whether night mode is starting.
+    ///
+    /// # Returns
+    /// The value to set, or [`None`] to leave the brightness alone.
+    pub fn dimming_for(&self, enabled: bool) -> Option<u8> {
+        if enabled {
+            self.dimming
+        } else {
+            self.day_dimming
+        }
+    }
+
+    /// Works out the local time of day that quiet hours are given in.
+    ///
+    /// # Arguments
+    /// * `now`: The current date and time.
+    ///
+    /// # Returns
+    /// The time of day at `utc_offset`.
+    pub fn local_time(&self, now: OffsetDateTime) -> Time {
+        now.to_offset(self.utc_offset.unwrap_or(UtcOffset::UTC))
+            .time()
+    }
+
+    /// Works out whether a time of day falls in the quiet hours. Quiet hours that pass midnight, e.g. `22:00` to
+    /// `07:00`, are handled.
+    ///
+    /// # Arguments
+    /// * `now`: Local time of day, from [`NightMode::local_time`].
+    ///
+    /// # Returns
+    /// Whether `now` is in the quiet hours, or [`None`] if they aren't set.
+    pub fn is_quiet(&self, now: Time) -> Option<bool> {
+        let (start, end) = (self.start?, self.end?);
+        Some(if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        })
+    }
+}
+
+/// Deserializes a time of day written as `HH:MM`.
+fn deserialize_time_of_day<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Time>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    time.split_once(':')
+        .and_then(|(hours, minutes)| Some((hours.parse().ok()?, minutes.parse().ok()?)))
+        .and_then(|(hours, minutes)| Time::from_hms(hours, minutes, 0).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid time of day `{time}`, expected e.g. `22:00`"
+            ))
+        })
+}
+
+/// Deserializes an offset from UTC written as `+HH:MM` or `-HH:MM`.
+fn deserialize_utc_offset<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<UtcOffset>, D::Error> {
+    let offset = String::deserialize(deserializer)?;
+    let signed = match offset.split_at(offset.len().min(1)) {
+        ("+", rest) => Some((1, rest)),
+        ("-", rest) => Some((-1, rest)),
+        _ => None,
+    };
+    signed
+        .and_then(|(sign, rest)| Some((sign, rest.split_once(':')?)))
+        .and_then(|(sign, (hours, minutes))| {
+            Some((sign, hours.parse::<i8>().ok()?, minutes.parse::<i8>().ok()?))
+        })
+        .and_then(|(sign, hours, minutes)| {
+            UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+        })
+        .map(Some)
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid UTC offset `{offset}`, expected e.g. `+01:00`"
+            ))
+        })
+}
+
+/// Checks on the sign's clock, which drifts on units whose clock backup capacitor is failing.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ClockCheck {
+    /// How often the sign's clock is read and compared with the host's, e.g. `10m`. It isn't checked if this isn't
+    /// set.
+    pub every: Option<HumanDuration>,
+    /// How far the sign's clock can drift from the host's, in local time at `night.utc_offset`, before a warning is
+    /// logged, e.g. `2m`.
+    pub max_drift: HumanDuration,
+}
+
+impl Default for ClockCheck {
+    fn default() -> Self {
+        Self {
+            every: None,
+            max_drift: HumanDuration(Duration::from_secs(120)),
+        }
+    }
+}
+
+/// One frame of a scene.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Frame {
+    /// Text to show.
+    pub text: String,
+    /// How long to show the frame for, e.g. `1.5s`, or in milliseconds if there is no unit.
+    #[serde(rename = "millis", deserialize_with = "duration::deserialize_millis")]
+    pub duration: Duration,
+}
+
+/// One step of the sequence shown when the service starts.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StartupStep {
+    /// Text to show.
+    pub text: String,
+    /// How long to show the text for before moving on to the next step, e.g. `1m 30s`, or in seconds if there is no
+    /// unit.
+    #[serde(default, rename = "seconds")]
+    pub duration: HumanDuration,
+}
+
+/// A text file, or a SMALL DOTS PICTURE file, in the sign's memory.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MemoryFile {
+    /// Label of the file.
+    pub label: char,
+    /// Size of a text file in bytes. Only the last file can be 0, which gives it the rest of the sign's memory.
+    #[serde(default)]
+    pub size: u16,
+    /// Makes the file a SMALL DOTS PICTURE of this size instead of a text file.
+    pub picture: Option<PictureSize>,
+}
+
+/// Size of a SMALL DOTS PICTURE file, in dots.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub struct PictureSize {
+    /// Width of the picture, in dots.
+    pub width: u8,
+    /// Height of the picture, in dots.
+    pub height: u8,
+}
+
+/// Text keys shown in big characters, e.g. for `OPEN` and `CLOSED`, drawn into a SMALL DOTS PICTURE file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Banner {
+    /// Keys whose text is shown in big characters.
+    pub keys: Vec<String>,
+    /// Label of the picture file the characters are drawn into. It needs laying out in `memory`, and its height sets
+    /// how big the characters are.
+    pub picture: char,
+}
+
+impl Default for Banner {
+    fn default() -> Self {
+        Self {
+            keys: vec![],
+            picture: 'Z',
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            text_keys: vec![
+                "test".to_string(),
+                "lulzbot".to_string(),
+                "anycubic".to_string(),
+            ],
+            routes: HashMap::new(),
+            default_route: None,
+            startup: vec![],
+            memory: vec![],
+            keyboard_access: false,
+            admin_token: None,
+            scenes: HashMap::new(),
+            night: NightMode::default(),
+            clock: ClockCheck::default(),
+            banner: Banner::default(),
+            random_mode_exclusions: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Looks up whether text PUT to a key is shown in big characters.
+    ///
+    /// # Arguments
+    /// * `text_key`: The key.
+    ///
+    /// # Returns
+    /// The picture to draw the text into, or [`None`] if it is shown as it is.
+    pub fn banner_for(&self, text_key: &str) -> Option<BannerPicture> {
+        if !self.banner.keys.iter().any(|key| key == text_key) {
+            return None;
+        }
+        let height = self
+            .memory
+            .iter()
+            .find(|file| file.label == self.banner.picture)
+            .and_then(|file| file.picture)
+            .map(|picture| picture.height);
+        Some(BannerPicture {
+            label: self.banner.picture,
+            height,
+        })
+    }
+}
+
+/// Errors that can occur when loading the config file.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not read config file: {0}")]
+    Io(std::io::Error),
+    #[error("could not parse config file: {0}")]
+    Parse(toml::de::Error),
+}
+
+impl Config {
+    /// Loads the config from a TOML file.
+    ///
+    /// # Arguments
+    /// * `path`: Path of the config file.
+    ///
+    /// # Returns
+    /// The loaded [`Config`].
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&contents).map_err(ConfigError::Parse)
+    }
+
+    /// Looks up which kinds of display text PUT to a key should be shown on.
+    ///
+    /// # Arguments
+    /// * `text_key`: The key text was PUT to.
+    ///
+    /// # Returns
+    /// The kinds of display to show the text on, or [`None`] to show it everywhere.
+    pub fn route(&self, text_key: &str) -> Option<&[Backend]> {
+        self.routes
+            .get(text_key)
+            .or(self.default_route.as_ref())
+            .map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a time of day for a test.
+    fn at(hours: u8, minutes: u8) -> Time {
+        Time::from_hms(hours, minutes, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let night = NightMode {
+            start: Some(at(9, 0)),
+            end: Some(at(17, 0)),
+            ..NightMode::default()
+        };
+        assert_eq!(night.is_quiet(at(8, 59)), Some(false));
+        assert_eq!(night.is_quiet(at(9, 0)), Some(true));
+        assert_eq!(night.is_quiet(at(17, 0)), Some(false));
+    }
+
+    #[test]
+    fn test_quiet_hours_past_midnight() {
+        let night = NightMode {
+            start: Some(at(22, 0)),
+            end: Some(at(7, 0)),
+            ..NightMode::default()
+        };
+        assert_eq!(night.is_quiet(at(23, 30)), Some(true));
+        assert_eq!(night.is_quiet(at(3, 0)), Some(true));
+        assert_eq!(night.is_quiet(at(12, 0)), Some(false));
+        assert_eq!(NightMode::default().is_quiet(at(23, 30)), None);
+    }
+
+    #[test]
+    fn test_quiet_hours_local_time() {
+        let night: NightMode = toml::from_str("utc_offset = \"+01:00\"").unwrap();
+        let now = OffsetDateTime::UNIX_EPOCH.replace_time(at(23, 30));
+        assert_eq!(night.local_time(now), at(0, 30));
+        assert_eq!(NightMode::default().local_time(now), at(23, 30));
+
+        let night: NightMode = toml::from_str("utc_offset = \"-05:30\"").unwrap();
+        assert_eq!(night.local_time(now), at(18, 0));
+
+        for offset in ["01:00", "+1", "+26:00", ""] {
+            let toml = format!("utc_offset = \"{offset}\"");
+            assert!(toml::from_str::<NightMode>(&toml).is_err(), "{offset}");
+        }
+    }
+
+    #[test]
+    fn test_parse_quiet_hours() {
+        let config: Config = toml::from_str("[night]\nstart = \"22:00\"\nend = \"07:30\"").unwrap();
+        assert_eq!(config.night.start, Some(at(22, 0)));
+        assert_eq!(config.night.end, Some(at(7, 30)));
+        assert!(toml::from_str::<Config>("[night]\nstart = \"25:00\"").is_err());
+    }
+
+    #[test]
+    fn test_banner_for() {
+        let config: Config = toml::from_str(
+            "[banner]\nkeys = [\"door\"]\n\n[[memory]]\nlabel = \"Z\"\npicture = { width = 80, height = 16 }",
+        )
+        .unwrap();
+        assert_eq!(
+            config.banner_for("door"),
+            Some(BannerPicture {
+                label: 'Z',
+                height: Some(16)
+            })
+        );
+        assert_eq!(config.banner_for("test"), None);
+    }
+
+    #[test]
+    fn test_parse_clock_check() {
+        let config: Config = toml::from_str("[clock]\nevery = \"10m\"").unwrap();
+        assert_eq!(
+            config.clock.every,
+            Some(HumanDuration(Duration::from_secs(600)))
+        );
+        assert_eq!(
+            config.clock.max_drift,
+            HumanDuration(Duration::from_secs(120))
+        );
+        assert_eq!(Config::default().clock.every, None);
+    }
+}
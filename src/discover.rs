@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use alpha_sign::{SignSelector, SignType};
+use serialport::SerialPort;
+
+use crate::serial;
+
+/// How long to wait for each address to answer. Shorter than the usual timeout so a scan of every address finishes in
+/// under a minute.
+const SCAN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Scans every sign address on a serial line and prints the signs that respond, along with their model if it can be
+/// worked out.
+///
+/// Responses from a sign don't say what model it is, so once a sign answers on an address it is asked again as each
+/// model in turn until it answers one of them.
+///
+/// # Arguments
+/// * `port`: The serial port the signs are on.
+///
+/// # Returns
+/// The address and model of each sign that responded.
+pub fn run(port: &mut Box<dyn SerialPort>) -> Vec<(u8, Option<SignType>)> {
+    if let Err(e) = port.set_timeout(SCAN_TIMEOUT) {
+        tracing::warn!("Failed to shorten the serial timeout, the scan will be slow: {e}");
+    }
+
+    println!("Scanning addresses 01 to FF...");
+    let mut found = vec![];
+    // Address 00 is the broadcast address, which every sign answers to.
+    for address in 0x01..=0xFF {
+        if !serial::sign_responds(port, SignSelector::new(SignType::All, address)) {
+            continue;
+        }
+        let model = SignType::models()
+            .find(|&sign_type| serial::sign_responds(port, SignSelector::new(sign_type, address)));
+        match model {
+            Some(model) => println!("{address:02X}  {model:?}"),
+            None => println!("{address:02X}  unknown model"),
+        }
+        found.push((address, model));
+    }
+
+    if found.is_empty() {
+        println!("No signs responded");
+    }
+    found
+}
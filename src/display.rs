@@ -0,0 +1,840 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+};
+
+use alpha_sign::{
+    read_special::ReadSpecial,
+    response::Response,
+    text::{call_dots, ReadText, TransitionMode, WriteText},
+    write_special::{
+        ColorStatus, ConfigureMemory, FileType, MemoryConfiguration, OnPeriod, SetDimmingRegister,
+        WriteSpecial,
+    },
+    Command, Packet, ProtocolProfile, ProtocolVersion, SignError, SignSelector,
+};
+use clap::ValueEnum;
+use serde::Deserialize;
+use thiserror::Error;
+use time::Time;
+
+use crate::{
+    config::MemoryFile,
+    flipdot,
+    recording::{Direction, Recorder},
+    serial::Pacer,
+    transport::Connection,
+};
+
+/// Which kind of display the service drives.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// An Alpha protocol sign on a serial port or TCP.
+    Alpha,
+    /// Text printed to the terminal, for running without any hardware.
+    Terminal,
+    /// A flipdot or LED matrix panel on a generic serial controller.
+    Flipdot,
+}
+
+/// A SMALL DOTS PICTURE file that text is drawn into in big characters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BannerPicture {
+    /// Label of the picture file.
+    pub label: char,
+    /// Height of the picture file in dots, if known, which the characters are scaled up to fill.
+    pub height: Option<u8>,
+}
+
+/// Errors that can occur when talking to a display.
+#[derive(Debug, Error)]
+pub enum DisplayError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode packet: {0}")]
+    Encoding(#[from] SignError),
+    #[error("display sent an invalid response")]
+    InvalidResponse,
+    #[error("invalid memory layout, only the last file can have a size of 0")]
+    InvalidMemoryLayout,
+}
+
+/// Works out how far a display's clock has drifted from the host's. Both are times of day, so drift past 12 hours
+/// is taken as the other way round midnight.
+///
+/// # Arguments
+/// * `display_time`: Time of day on the display's clock, from [`DisplayBackend::read_time`].
+/// * `host_time`: Time of day on the host's clock, in the same time zone.
+///
+/// # Returns
+/// Whole minutes the display's clock is ahead, or behind if negative. Seconds are ignored as displays only give the
+/// time to the minute.
+pub fn clock_drift(display_time: Time, host_time: Time) -> i64 {
+    const MINUTES_PER_DAY: i64 = 24 * 60;
+    let minutes = |time: Time| i64::from(time.hour()) * 60 + i64::from(time.minute());
+    let drift = (minutes(display_time) - minutes(host_time)).rem_euclid(MINUTES_PER_DAY);
+    if drift > MINUTES_PER_DAY / 2 {
+        drift - MINUTES_PER_DAY
+    } else {
+        drift
+    }
+}
+
+/// Something text can be shown on.
+pub trait DisplayBackend: Send {
+    /// Writes text to a file on the display.
+    ///
+    /// # Arguments
+    /// * `text`: The text to write. Backends that don't support positions or transition modes ignore them.
+    fn write_text(&mut self, text: WriteText) -> Result<(), DisplayError>;
+
+    /// Writes text to a file on the display in big characters, drawn into a picture. Displays that can't show pictures
+    /// show the text as it is.
+    ///
+    /// # Arguments
+    /// * `text`: The text to write.
+    /// * `picture`: The picture file to draw the characters into.
+    fn write_banner(
+        &mut self,
+        text: WriteText,
+        _picture: BannerPicture,
+    ) -> Result<(), DisplayError> {
+        self.write_text(text)
+    }
+
+    /// Reads back the text held in a file on the display.
+    ///
+    /// # Arguments
+    /// * `read`: Which file to read.
+    ///
+    /// # Returns
+    /// The text in the file.
+    fn read_text(&mut self, read: ReadText) -> Result<String, DisplayError>;
+
+    /// Lays out the display's memory as the given text files, keeping the text in any of them that already exist.
+    /// Displays without memory to lay out do nothing.
+    ///
+    /// # Arguments
+    /// * `files`: The files to lay out, in order.
+    /// * `keyboard_access`: Whether the files can be edited with the display's keyboard, if it has one.
+    fn configure_memory(
+        &mut self,
+        _files: &[MemoryFile],
+        _keyboard_access: bool,
+    ) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    /// Sets how bright the display is. Displays that can't be dimmed do nothing.
+    ///
+    /// # Arguments
+    /// * `level`: Value for the display's dimming register.
+    fn set_dimming(&mut self, _level: u8) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    /// Runs one of the display's special functions, e.g. a soft reset. Displays without special functions do nothing.
+    ///
+    /// # Arguments
+    /// * `special`: The special function to run.
+    fn write_special(&mut self, _special: WriteSpecial) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    /// Reads the display's clock.
+    ///
+    /// # Returns
+    /// The time of day on the display's clock, to the minute, or [`None`] if it doesn't have one.
+    fn read_time(&mut self) -> Result<Option<Time>, DisplayError> {
+        Ok(None)
+    }
+
+    /// Whether the display can show text with the given transition mode. Displays that ignore modes support them all.
+    ///
+    /// # Arguments
+    /// * `mode`: The transition mode.
+    fn supports_mode(&self, _mode: TransitionMode) -> bool {
+        true
+    }
+}
+
+/// An Alpha protocol sign connected over a serial port or TCP.
+pub struct AlphaBackend {
+    sign: SignSelector,
+    port: Box<dyn Connection>,
+    profile: ProtocolProfile,
+    recorder: Option<Recorder>,
+    pacer: Option<Pacer>,
+}
+
+impl AlphaBackend {
+    /// Creates a new [`AlphaBackend`].
+    ///
+    /// # Arguments
+    /// * `sign`: The sign to address.
+    /// * `port`: The connection to the sign.
+    ///
+    /// # Returns
+    /// A new [`AlphaBackend`].
+    pub fn new(sign: SignSelector, port: Box<dyn Connection>) -> Self {
+        Self {
+            sign,
+            port,
+            profile: ProtocolProfile::for_version(ProtocolVersion::LATEST),
+            recorder: None,
+            pacer: None,
+        }
+    }
+
+    /// Sets the version of the protocol the sign speaks. Commands the sign doesn't understand fail to encode rather
+    /// than being sent, and text using features it doesn't have is downgraded to ones it does.
+    ///
+    /// # Arguments
+    /// * `protocol`: Version of the protocol the sign speaks.
+    pub fn protocol(mut self, protocol: ProtocolVersion) -> Self {
+        self.profile = ProtocolProfile::for_version(protocol);
+        self
+    }
+
+    /// Works out what the sign can do by asking it how its memory is laid out and whether it has a serial error status
+    /// register. Signs that don't answer are taken to speak `fallback`.
+    ///
+    /// # Arguments
+    /// * `fallback`: Version of the protocol the sign is taken to speak if it doesn't answer.
+    pub fn negotiate(mut self, fallback: ProtocolVersion) -> Self {
+        // Reads are in every version of the protocol, so they can be sent before the version is known
+        self.profile = ProtocolProfile::for_version(ProtocolVersion::Alpha1);
+        let configurations = match self.read_special(ReadSpecial::MemoryConfiguration) {
+            Ok(Response::MemoryConfiguration(configurations)) => configurations,
+            res => {
+                if let Err(e) = res {
+                    tracing::debug!("Reading memory configuration failed: {e}");
+                }
+                tracing::warn!(
+                    "Sign didn't say what it can do, taking it to speak protocol {fallback:?}"
+                );
+                self.profile = ProtocolProfile::for_version(fallback);
+                return self;
+            }
+        };
+        //TODO confirm, Alpha 1.0 signs don't seem to have the serial error status register. Alpha 3.0 signs can't be
+        // told from 2.0 ones this way, so `fallback` decides between them.
+        let version = match self.read_special(ReadSpecial::SerialErrorStatus) {
+            Ok(Response::SerialErrorStatus(_)) => fallback.max(ProtocolVersion::Alpha2),
+            _ => ProtocolVersion::Alpha1,
+        };
+        self.profile = ProtocolProfile::from_memory_configuration(version, &configurations);
+        tracing::info!("Negotiated {:?}", self.profile);
+        self
+    }
+
+    /// Records all traffic to and from the sign.
+    ///
+    /// # Arguments
+    /// * `recorder`: Where to record traffic to.
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Spaces packets out to suit the speed of the serial line to the sign.
+    ///
+    /// # Arguments
+    /// * `pacer`: Pacer for the line.
+    pub fn pacer(mut self, pacer: Pacer) -> Self {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    /// Sends a packet to the sign.
+    ///
+    /// # Arguments
+    /// * `packet`: The packet to send.
+    fn send(&mut self, packet: Packet) -> Result<(), DisplayError> {
+        tracing::debug!("Sending {packet}");
+        let bytes = packet.encode_for(self.profile.version)?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(Direction::Tx, bytes.as_slice());
+        }
+        if let Some(pacer) = &mut self.pacer {
+            pacer.wait();
+        }
+        self.port.write_all(bytes.as_slice())?;
+        if let Some(pacer) = &mut self.pacer {
+            pacer.sent(bytes.len());
+        }
+        Ok(())
+    }
+
+    /// Reads the state of one of the sign's special functions, e.g. how its memory is laid out.
+    ///
+    /// # Arguments
+    /// * `read`: The special function to read.
+    ///
+    /// # Returns
+    /// The sign's answer.
+    pub fn read_special(&mut self, read: ReadSpecial) -> Result<Response, DisplayError> {
+        self.send(Packet::new(
+            vec![self.sign],
+            vec![Command::ReadSpecial(read)],
+        ))?;
+        self.receive()?
+            .commands
+            .into_iter()
+            .next()
+            .and_then(Response::classify)
+            .ok_or(DisplayError::InvalidResponse)
+    }
+
+    /// Reads back a text file along with its position and transition mode.
+    ///
+    /// # Arguments
+    /// * `read`: Which file to read.
+    ///
+    /// # Returns
+    /// The file as it would be written.
+    fn read_text_file(&mut self, read: ReadText) -> Result<WriteText, DisplayError> {
+        self.send(Packet::new(vec![self.sign], vec![Command::ReadText(read)]))?;
+        match self
+            .receive()?
+            .commands
+            .into_iter()
+            .next()
+            .and_then(Response::classify)
+        {
+            Some(Response::Text(text)) => Ok(text),
+            _ => Err(DisplayError::InvalidResponse),
+        }
+    }
+
+    /// Waits for a packet from the sign.
+    ///
+    /// # Returns
+    /// The packet the sign sent.
+    fn receive(&mut self) -> Result<Packet, DisplayError> {
+        let mut buf: Vec<u8> = vec![];
+        let res = BufReader::new(&mut self.port).read_until(0x04, &mut buf);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(Direction::Rx, buf.as_slice());
+        }
+        res?;
+
+        let (_, packet) =
+            Packet::parse(buf.as_slice()).map_err(|_| DisplayError::InvalidResponse)?;
+        tracing::debug!("Received {packet}");
+        Ok(packet)
+    }
+}
+
+impl DisplayBackend for AlphaBackend {
+    fn write_text(&mut self, text: WriteText) -> Result<(), DisplayError> {
+        let (text, changes) = self.profile.downgrade(text);
+        for change in changes {
+            tracing::warn!("Downgrading text for file {}: {change}", text.label);
+        }
+        self.send(Packet::new(vec![self.sign], vec![Command::WriteText(text)]))
+    }
+
+    fn write_banner(
+        &mut self,
+        mut text: WriteText,
+        picture: BannerPicture,
+    ) -> Result<(), DisplayError> {
+        if !self.profile.dots_pictures {
+            tracing::warn!(
+                "Sign has no picture files, showing banner text for file {} as it is",
+                text.label
+            );
+            return self.write_text(text);
+        }
+        let dots = flipdot::banner(picture, &text.message)?;
+        text.message = call_dots(picture.label);
+        let (text, changes) = self.profile.downgrade(text);
+        for change in changes {
+            tracing::warn!("Downgrading text for file {}: {change}", text.label);
+        }
+        self.send(Packet::new(
+            vec![self.sign],
+            vec![
+                Command::WriteSmallDotsPicture(dots),
+                Command::WriteText(text),
+            ],
+        ))
+    }
+
+    fn read_text(&mut self, read: ReadText) -> Result<String, DisplayError> {
+        Ok(self.read_text_file(read)?.message)
+    }
+
+    fn configure_memory(
+        &mut self,
+        files: &[MemoryFile],
+        keyboard_access: bool,
+    ) -> Result<(), DisplayError> {
+        let configurations = files
+            .iter()
+            .map(|file| {
+                let file_type = match file.picture {
+                    // Banners are drawn in one colour
+                    Some(picture) => FileType::Dots {
+                        x: picture.width,
+                        y: picture.height,
+                        color_status: ColorStatus::Monochrome,
+                    },
+                    None => FileType::Text {
+                        size: file.size,
+                        on_period: OnPeriod::Always,
+                    },
+                };
+                MemoryConfiguration::new(file.label, file_type, keyboard_access)
+            })
+            .collect();
+        let configure =
+            ConfigureMemory::new(configurations).map_err(|_| DisplayError::InvalidMemoryLayout)?;
+
+        // Configuring memory wipes every file on the sign, so read back what's there first and put it back after. The
+        // files are the ones in the sign's current layout, which may not be the same as the new one.
+        let labels = match self.read_special(ReadSpecial::MemoryConfiguration) {
+            Ok(Response::MemoryConfiguration(current)) => {
+                let mut labels = vec![];
+                for configuration in current {
+                    let kept = files
+                        .iter()
+                        .any(|file| file.label == configuration.label && file.picture.is_none());
+                    match configuration.file_type {
+                        FileType::Text { .. } if kept => labels.push(configuration.label),
+                        FileType::Text { .. } => tracing::warn!(
+                            "File {} isn't in the new layout, its text will be lost",
+                            configuration.label
+                        ),
+                        _ => tracing::warn!(
+                            "File {} isn't a text file, its contents will be lost",
+                            configuration.label
+                        ),
+                    }
+                }
+                labels
+            }
+            res => {
+                if let Err(e) = res {
+                    tracing::warn!("Failed to read the sign's memory layout: {e}");
+                }
+                tracing::warn!(
+                    "Backing up the files in the new layout only, any others on the sign will be lost"
+                );
+                files
+                    .iter()
+                    .filter(|file| file.picture.is_none())
+                    .map(|file| file.label)
+                    .collect()
+            }
+        };
+        let mut backup = vec![];
+        for label in labels {
+            match self.read_text_file(ReadText::new(label)) {
+                Ok(text) => backup.push(text),
+                Err(e) => tracing::warn!("Failed to back up file {label}, it will be empty: {e}"),
+            }
+        }
+
+        self.send(Packet::new(
+            vec![self.sign],
+            vec![Command::WriteSpecial(WriteSpecial::ConfigureMemory(
+                configure,
+            ))],
+        ))?;
+
+        // Put back each file as it was, including its position and transition mode.
+        for text in backup {
+            if !text.message.is_empty() {
+                self.write_text(text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn supports_mode(&self, mode: TransitionMode) -> bool {
+        self.profile.supports_mode(mode)
+    }
+
+    fn set_dimming(&mut self, level: u8) -> Result<(), DisplayError> {
+        self.write_special(WriteSpecial::SetDimmingRegister(SetDimmingRegister::new(
+            level,
+        )))
+    }
+
+    fn write_special(&mut self, special: WriteSpecial) -> Result<(), DisplayError> {
+        self.send(Packet::new(
+            vec![self.sign],
+            vec![Command::WriteSpecial(special)],
+        ))
+    }
+
+    fn read_time(&mut self) -> Result<Option<Time>, DisplayError> {
+        match self.read_special(ReadSpecial::Time)? {
+            Response::Time(time) => Ok(Some(time)),
+            _ => Err(DisplayError::InvalidResponse),
+        }
+    }
+}
+
+/// Prints text to the terminal instead of driving real hardware.
+#[derive(Default)]
+pub struct TerminalBackend {
+    files: HashMap<char, String>,
+}
+
+impl TerminalBackend {
+    /// Creates a new [`TerminalBackend`] with no files written.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DisplayBackend for TerminalBackend {
+    fn write_text(&mut self, text: WriteText) -> Result<(), DisplayError> {
+        println!(
+            "\x1b[1;31m[{}]\x1b[0m \x1b[1;33m{}\x1b[0m",
+            text.label, text.message
+        );
+        self.files.insert(text.label, text.message);
+        Ok(())
+    }
+
+    fn read_text(&mut self, read: ReadText) -> Result<String, DisplayError> {
+        Ok(self.files.get(&read.label).cloned().unwrap_or_default())
+    }
+}
+
+/// Shows the same text on several displays at once, optionally routing text to only some of them.
+pub struct MirroredBackend {
+    displays: Vec<(Backend, Box<dyn DisplayBackend>)>,
+}
+
+impl MirroredBackend {
+    /// Creates a new [`MirroredBackend`].
+    ///
+    /// # Arguments
+    /// * `displays`: The displays to mirror text across, along with what kind they are. Text is read back from the
+    ///   first one.
+    ///
+    /// # Returns
+    /// A new [`MirroredBackend`].
+    pub fn new(displays: Vec<(Backend, Box<dyn DisplayBackend>)>) -> Self {
+        Self { displays }
+    }
+
+    /// Writes text to only the displays of the given kinds.
+    ///
+    /// # Arguments
+    /// * `text`: The text to write.
+    /// * `targets`: Kinds of display to write to.
+    pub fn write_text_to(
+        &mut self,
+        text: WriteText,
+        targets: &[Backend],
+    ) -> Result<(), DisplayError> {
+        self.write_to(targets, |display| display.write_text(text.clone()))
+    }
+
+    /// Writes text in big characters to only the displays of the given kinds.
+    ///
+    /// # Arguments
+    /// * `text`: The text to write.
+    /// * `picture`: The picture file to draw the characters into.
+    /// * `targets`: Kinds of display to write to.
+    pub fn write_banner_to(
+        &mut self,
+        text: WriteText,
+        picture: BannerPicture,
+        targets: &[Backend],
+    ) -> Result<(), DisplayError> {
+        self.write_to(targets, |display| {
+            display.write_banner(text.clone(), picture)
+        })
+    }
+
+    /// Writes to the displays of the given kinds.
+    ///
+    /// # Arguments
+    /// * `targets`: Kinds of display to write to.
+    /// * `write`: Writes to one display.
+    fn write_to(
+        &mut self,
+        targets: &[Backend],
+        mut write: impl FnMut(&mut dyn DisplayBackend) -> Result<(), DisplayError>,
+    ) -> Result<(), DisplayError> {
+        // Keep going if one display fails so the others still show the text, but report the first error.
+        let mut res = Ok(());
+        for (backend, display) in &mut self.displays {
+            if !targets.contains(backend) {
+                continue;
+            }
+            if let Err(e) = write(display.as_mut()) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+
+    /// Kinds of all the displays.
+    fn backends(&self) -> Vec<Backend> {
+        self.displays.iter().map(|(backend, _)| *backend).collect()
+    }
+}
+
+impl DisplayBackend for MirroredBackend {
+    fn write_text(&mut self, text: WriteText) -> Result<(), DisplayError> {
+        let targets = self.backends();
+        self.write_text_to(text, &targets)
+    }
+
+    fn write_banner(
+        &mut self,
+        text: WriteText,
+        picture: BannerPicture,
+    ) -> Result<(), DisplayError> {
+        let targets = self.backends();
+        self.write_banner_to(text, picture, &targets)
+    }
+
+    fn read_text(&mut self, read: ReadText) -> Result<String, DisplayError> {
+        match self.displays.first_mut() {
+            Some((_, display)) => display.read_text(read),
+            None => Ok(String::new()),
+        }
+    }
+
+    fn configure_memory(
+        &mut self,
+        files: &[MemoryFile],
+        keyboard_access: bool,
+    ) -> Result<(), DisplayError> {
+        let mut res = Ok(());
+        for (_, display) in &mut self.displays {
+            if let Err(e) = display.configure_memory(files, keyboard_access) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+
+    fn set_dimming(&mut self, level: u8) -> Result<(), DisplayError> {
+        let mut res = Ok(());
+        for (_, display) in &mut self.displays {
+            if let Err(e) = display.set_dimming(level) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+
+    fn write_special(&mut self, special: WriteSpecial) -> Result<(), DisplayError> {
+        let mut res = Ok(());
+        for (_, display) in &mut self.displays {
+            if let Err(e) = display.write_special(special.clone()) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+
+    fn read_time(&mut self) -> Result<Option<Time>, DisplayError> {
+        // The first display with a clock is the one checked.
+        for (_, display) in &mut self.displays {
+            if let Some(time) = display.read_time()? {
+                return Ok(Some(time));
+            }
+        }
+        Ok(None)
+    }
+
+    fn supports_mode(&self, mode: TransitionMode) -> bool {
+        self.displays
+            .iter()
+            .all(|(_, display)| display.supports_mode(mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alpha_sign::write_special::{SerialErrorStatus, SetTime};
+
+    use super::*;
+    use crate::transport::FakeConnection;
+
+    #[test]
+    fn test_read_time() {
+        let port = FakeConnection::default();
+        let answer = Packet::new(
+            vec![SignSelector::default()],
+            vec![Command::WriteSpecial(WriteSpecial::SetTime(SetTime::new(
+                Time::from_hms(21, 45, 0).unwrap(),
+            )))],
+        );
+        port.to_read
+            .lock()
+            .unwrap()
+            .extend(answer.encode().unwrap());
+        let mut display = MirroredBackend::new(vec![
+            (Backend::Terminal, Box::new(TerminalBackend::new())),
+            (
+                Backend::Alpha,
+                Box::new(AlphaBackend::new(
+                    SignSelector::default(),
+                    Box::new(port.clone()),
+                )),
+            ),
+        ]);
+
+        assert_eq!(
+            display.read_time().unwrap(),
+            Some(Time::from_hms(21, 45, 0).unwrap())
+        );
+        let read = Packet::new(
+            vec![SignSelector::default()],
+            vec![Command::ReadSpecial(ReadSpecial::Time)],
+        );
+        assert_eq!(*port.written.lock().unwrap(), read.encode().unwrap());
+    }
+
+    /// Queues up the packets a sign sends in answer to reads.
+    fn answer(port: &FakeConnection, answers: Vec<WriteSpecial>) {
+        for answer in answers {
+            let packet = Packet::new(
+                vec![SignSelector::default()],
+                vec![Command::WriteSpecial(answer)],
+            );
+            port.to_read
+                .lock()
+                .unwrap()
+                .extend(packet.encode().unwrap());
+        }
+    }
+
+    /// Memory laid out as one 64 character text file, as a sign would send it back.
+    fn memory_layout() -> WriteSpecial {
+        let Ok(memory) = ConfigureMemory::new(vec![MemoryConfiguration::new(
+            'A',
+            FileType::Text {
+                size: 64,
+                on_period: OnPeriod::Always,
+            },
+            false,
+        )]) else {
+            panic!("layout should fit");
+        };
+        WriteSpecial::ConfigureMemory(memory)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let port = FakeConnection::default();
+        answer(
+            &port,
+            vec![
+                memory_layout(),
+                WriteSpecial::SerialErrorStatus(SerialErrorStatus::empty()),
+            ],
+        );
+
+        let alpha = AlphaBackend::new(SignSelector::default(), Box::new(port))
+            .negotiate(ProtocolVersion::Alpha3);
+        assert_eq!(alpha.profile.version, ProtocolVersion::Alpha3);
+        assert_eq!(alpha.profile.max_text_len, Some(64));
+        assert!(!alpha.profile.dots_pictures);
+    }
+
+    #[test]
+    fn test_negotiate_alpha1() {
+        let port = FakeConnection::default();
+        answer(&port, vec![memory_layout()]);
+
+        let alpha = AlphaBackend::new(SignSelector::default(), Box::new(port))
+            .negotiate(ProtocolVersion::Alpha3);
+        assert_eq!(alpha.profile.version, ProtocolVersion::Alpha1);
+        assert_eq!(alpha.profile.max_text_len, Some(64));
+    }
+
+    #[test]
+    fn test_negotiate_falls_back() {
+        let alpha = AlphaBackend::new(SignSelector::default(), Box::new(FakeConnection::default()))
+            .negotiate(ProtocolVersion::Alpha2);
+        assert_eq!(
+            alpha.profile,
+            ProtocolProfile::for_version(ProtocolVersion::Alpha2)
+        );
+    }
+
+    #[test]
+    fn test_write_banner() {
+        let port = FakeConnection::default();
+        let mut alpha = AlphaBackend::new(SignSelector::default(), Box::new(port.clone()));
+        let picture = BannerPicture {
+            label: 'Z',
+            height: None,
+        };
+        alpha
+            .write_banner(WriteText::new('A', "OPEN".to_string()), picture)
+            .unwrap();
+
+        // The picture is written, then called up from the text file
+        let expected = Packet::new(
+            vec![SignSelector::default()],
+            vec![
+                Command::WriteSmallDotsPicture(flipdot::banner(picture, "OPEN").unwrap()),
+                Command::WriteText(WriteText::new('A', call_dots('Z'))),
+            ],
+        );
+        assert_eq!(*port.written.lock().unwrap(), expected.encode().unwrap());
+    }
+
+    #[test]
+    fn test_write_banner_without_pictures() {
+        let port = FakeConnection::default();
+        answer(&port, vec![memory_layout()]);
+        let mut alpha = AlphaBackend::new(SignSelector::default(), Box::new(port.clone()))
+            .negotiate(ProtocolVersion::Alpha3);
+        port.written.lock().unwrap().clear();
+
+        let text = WriteText::new('A', "OPEN".to_string());
+        let picture = BannerPicture {
+            label: 'Z',
+            height: None,
+        };
+        alpha.write_banner(text.clone(), picture).unwrap();
+        let expected = Packet::new(
+            vec![SignSelector::default()],
+            vec![Command::WriteText(text)],
+        );
+        assert_eq!(
+            *port.written.lock().unwrap(),
+            expected.encode_for(ProtocolVersion::Alpha1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clock_drift() {
+        let at = |hours, minutes, seconds| Time::from_hms(hours, minutes, seconds).unwrap();
+        assert_eq!(clock_drift(at(12, 0, 0), at(12, 0, 59)), 0);
+        assert_eq!(clock_drift(at(12, 5, 0), at(12, 0, 0)), 5);
+        assert_eq!(clock_drift(at(11, 57, 0), at(12, 0, 0)), -3);
+        assert_eq!(clock_drift(at(0, 1, 0), at(23, 59, 0)), 2);
+        assert_eq!(clock_drift(at(23, 58, 0), at(0, 2, 0)), -4);
+    }
+
+    #[test]
+    fn test_read_time_without_clock() {
+        let mut display = TerminalBackend::new();
+        assert_eq!(display.read_time().unwrap(), None);
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use alpha_sign::{
+    read_special::ReadSpecial,
+    response::Response,
+    text::{ReadText, WriteText},
+    write_special::FileType,
+    SignSelector,
+};
+
+use crate::{
+    config::MemoryFile,
+    display::{AlphaBackend, DisplayBackend},
+    serial::{self, Baudrate, Framing},
+};
+
+/// Text written to the sign to check that messages round trip.
+const TEST_MESSAGE: &str = "YHS sign doctor";
+
+/// Outcome of a single check.
+enum Check {
+    Ok(String),
+    Skipped(String),
+    /// Working, but in a way that will stop working in future.
+    Warning {
+        problem: String,
+        fix: String,
+    },
+    Failed {
+        problem: String,
+        fix: String,
+    },
+}
+
+impl Check {
+    fn warning(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check::Warning {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+
+    fn failed(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check::Failed {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+
+    fn print(&self) {
+        match self {
+            Check::Ok(message) => println!("[ ok ] {message}"),
+            Check::Skipped(message) => println!("[skip] {message}"),
+            Check::Warning { problem, fix } => {
+                println!("[warn] {problem}");
+                println!("       fix: {fix}");
+            }
+            Check::Failed { problem, fix } => {
+                println!("[FAIL] {problem}");
+                println!("       fix: {fix}");
+            }
+        }
+    }
+}
+
+/// Runs a series of checks on the serial port, sign and HTTP service and prints a diagnosis of each.
+///
+/// # Arguments
+/// * `path`: Path of the serial device the sign is connected to.
+/// * `baudrate`: Baud rate to talk to the sign at, or [`Baudrate::Auto`] to try each supported rate.
+/// * `framings`: Framings to try, in order.
+/// * `sign`: The sign to address.
+/// * `memory`: The memory layout from the config file, empty if none is set.
+/// * `http_port`: Port the HTTP API is expected to be listening on.
+///
+/// # Returns
+/// Whether every check passed.
+pub fn run(
+    path: &str,
+    baudrate: Baudrate,
+    framings: &[Framing],
+    sign: SignSelector,
+    memory: &[MemoryFile],
+    http_port: u16,
+) -> bool {
+    let mut healthy = true;
+    let mut report = |check: Check| {
+        if matches!(check, Check::Failed { .. }) {
+            healthy = false;
+        }
+        check.print();
+    };
+
+    let sign_port = match check_port(path, framings[0]) {
+        Ok(check) => {
+            report(check);
+            let baudrates = match baudrate {
+                Baudrate::Auto => Baudrate::SUPPORTED.to_vec(),
+                Baudrate::Fixed(baudrate) => vec![baudrate],
+            };
+            match serial::probe(path, &baudrates, framings, sign) {
+                Some((port, baudrate, framing)) => {
+                    report(Check::Ok(format!(
+                        "Sign responded at {baudrate} baud using {framing} framing"
+                    )));
+                    Some(port)
+                }
+                None => {
+                    report(Check::failed(
+                        "Sign did not respond to any of the probed serial settings",
+                        "check the sign is powered on and cabled to this port, then try `--baudrate auto --probe-framing`",
+                    ));
+                    None
+                }
+            }
+        }
+        Err(check) => {
+            report(check);
+            None
+        }
+    };
+
+    match sign_port {
+        Some(port) => {
+            let mut alpha = AlphaBackend::new(sign, Box::new(port));
+            report(check_memory(&mut alpha, memory));
+            report(check_round_trip(&mut alpha));
+        }
+        None => {
+            report(Check::Skipped(
+                "Memory configuration, the sign isn't reachable".to_string(),
+            ));
+            report(Check::Skipped(
+                "Message round trip, the sign isn't reachable".to_string(),
+            ));
+        }
+    }
+
+    report(check_http(http_port));
+
+    healthy
+}
+
+/// Checks the serial device exists and can be opened by this user.
+///
+/// # Arguments
+/// * `path`: Path of the serial device.
+/// * `framing`: Framing to open the port with.
+///
+/// # Returns
+/// The passing check, or the failing one as an error.
+fn check_port(path: &str, framing: Framing) -> Result<Check, Check> {
+    if !Path::new(path).exists() {
+        return Err(Check::failed(
+            format!("Serial port {path} does not exist"),
+            "check the USB serial adapter is plugged in and pass its device with `--port`",
+        ));
+    }
+
+    match serial::open(path, Baudrate::SUPPORTED[0], framing) {
+        Ok(_) => Ok(Check::Ok(format!("Serial port {path} can be opened"))),
+        Err(e) => match e.kind() {
+            serialport::ErrorKind::Io(ErrorKind::PermissionDenied) => Err(Check::failed(
+                format!("Permission denied opening {path}"),
+                "add this user to the group owning the port, e.g. `sudo usermod -aG dialout $USER`, then log in again",
+            )),
+            _ => Err(Check::failed(
+                format!("Failed to open {path}: {e}"),
+                "check nothing else (e.g. the running big-sign service) has the port open",
+            )),
+        },
+    }
+}
+
+/// Checks the sign's memory is laid out as set by `memory` in the config file.
+///
+/// # Arguments
+/// * `alpha`: The sign.
+/// * `memory`: The memory layout from the config file, empty if none is set.
+///
+/// # Returns
+/// The result of the check.
+fn check_memory(alpha: &mut AlphaBackend, memory: &[MemoryFile]) -> Check {
+    let configurations = match alpha.read_special(ReadSpecial::MemoryConfiguration) {
+        Ok(Response::MemoryConfiguration(configurations)) => configurations,
+        Ok(_) => {
+            return Check::failed(
+                "Sign answered a read of its memory configuration with something else",
+                "the sign answered the probe but not a read, try power cycling it",
+            )
+        }
+        Err(e) => {
+            return Check::failed(
+                format!("Failed to read the memory configuration from the sign: {e}"),
+                "the sign answered the probe but not a read, try power cycling it",
+            )
+        }
+    };
+
+    if memory.is_empty() {
+        return Check::Ok(format!(
+            "Sign's memory is laid out as {} files, no `memory` layout in the config file to compare with",
+            configurations.len()
+        ));
+    }
+
+    let matches = configurations.len() == memory.len()
+        && configurations
+            .iter()
+            .zip(memory)
+            .all(|(configuration, file)| {
+                configuration.label == file.label
+                    && match configuration.file_type {
+                        // A size of 0 gives the file the rest of memory, so the sign reports however much that was
+                        FileType::Text { size, .. } => {
+                            file.picture.is_none() && (file.size == 0 || size == file.size)
+                        }
+                        FileType::Dots { x, y, .. } => file
+                            .picture
+                            .is_some_and(|picture| (picture.width, picture.height) == (x, y)),
+                        _ => false,
+                    }
+            });
+    if matches {
+        Check::Ok(format!(
+            "Sign's memory is laid out as the {} files in the config file",
+            memory.len()
+        ))
+    } else {
+        let labels: String = configurations
+            .iter()
+            .map(|configuration| configuration.label)
+            .collect();
+        Check::failed(
+            format!("Sign's memory layout (files {labels:?}) doesn't match `memory` in the config file"),
+            "stop the service and run `yhs-sign --config <file> reprovision`, which keeps the text in existing files",
+        )
+    }
+}
+
+/// Writes a test message to the sign and reads it back, putting back whatever was there before.
+///
+/// # Arguments
+/// * `alpha`: The sign.
+///
+/// # Returns
+/// The result of the check.
+fn check_round_trip(alpha: &mut AlphaBackend) -> Check {
+    let previous = match alpha.read_text(ReadText::new('A')) {
+        Ok(previous) => previous,
+        Err(e) => {
+            return Check::failed(
+                format!("Failed to read text from the sign: {e}"),
+                "the sign answered the probe but not a read, try power cycling it",
+            )
+        }
+    };
+
+    let res = alpha
+        .write_text(WriteText::new('A', TEST_MESSAGE.to_string()))
+        .and_then(|()| alpha.read_text(ReadText::new('A')));
+    if let Err(e) = alpha.write_text(WriteText::new('A', previous)) {
+        tracing::warn!("Failed to restore text on the sign: {e}");
+    }
+
+    match res {
+        Ok(text) if text == TEST_MESSAGE => Check::Ok("Test message round tripped".to_string()),
+        Ok(text) => Check::failed(
+            format!("Sign read back {text:?} instead of the test message"),
+            "the sign's memory may be misconfigured, clear it from the keyboard and try again",
+        ),
+        Err(e) => Check::failed(
+            format!("Failed to round trip a test message: {e}"),
+            "check the cable, a loose connection often shows up as garbled responses",
+        ),
+    }
+}
+
+/// Checks the HTTP API is answering on the API port, at `/api/v1` or failing that the deprecated unversioned paths.
+///
+/// # Arguments
+/// * `port`: Port the HTTP API is expected to be listening on.
+///
+/// # Returns
+/// The result of the check.
+fn check_http(port: u16) -> Check {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let fix =
+        "start the service with `systemctl start big-sign` and check `journalctl -u big-sign`";
+
+    match http_get(addr, "/api/v1/status") {
+        Ok(Some(200)) => return Check::Ok(format!("HTTP API is answering on {addr}")),
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Check::failed(
+                format!("Something other than HTTP is listening on {addr}"),
+                fix,
+            )
+        }
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused || e.kind() == ErrorKind::TimedOut => {
+            return Check::failed(format!("Nothing listening on {addr}: {e}"), fix)
+        }
+        Err(e) => return Check::failed(format!("HTTP API on {addr} did not answer: {e}"), fix),
+    }
+
+    // Older versions of the service only serve the unversioned paths.
+    match http_get(addr, "/status") {
+        Ok(Some(200)) => Check::warning(
+            format!("HTTP API on {addr} only answers on the deprecated unversioned paths"),
+            "update the service, then move any scripts over to the `/api/v1` paths",
+        ),
+        Ok(Some(status)) => Check::failed(
+            format!("HTTP API on {addr} answered {status} to a status request"),
+            fix,
+        ),
+        Ok(None) => Check::failed(
+            format!("Something other than HTTP is listening on {addr}"),
+            fix,
+        ),
+        Err(e) => Check::failed(format!("HTTP API on {addr} did not answer: {e}"), fix),
+    }
+}
+
+/// Sends a GET to the HTTP API and reads the status code it answers with.
+///
+/// # Arguments
+/// * `addr`: Address the HTTP API is listening on.
+/// * `path`: Path to GET.
+///
+/// # Returns
+/// The status code, or [`None`] if the answer wasn't HTTP.
+fn http_get(addr: SocketAddr, path: &str) -> io::Result<Option<u16>> {
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(2))?;
+    stream.set_read_timeout(Some(Duration::from_secs(2))).ok();
+
+    // e.g. `HTTP/1.1 200`
+    let mut status = [0u8; 12];
+    stream.write_all(format!("GET {path} HTTP/1.0\r\n\r\n").as_bytes())?;
+    stream.read_exact(&mut status)?;
+    if !status.starts_with(b"HTTP/1.") {
+        return Ok(None);
+    }
+    Ok(std::str::from_utf8(&status[9..])
+        .ok()
+        .and_then(|code| code.parse().ok()))
+}
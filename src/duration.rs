@@ -0,0 +1,154 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde::{de, Deserialize, Deserializer};
+
+/// A duration given by a person, e.g. `90s`, `1m 30s` or `2h`. A number on its own is taken as seconds, so callers
+/// that already send raw seconds keep working.
+///
+/// Deserializes from either a string or a whole number of seconds, so it can be used for JSON fields and query
+/// parameters alike.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HumanDuration(pub Duration);
+
+impl FromStr for HumanDuration {
+    type Err = humantime::DurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return humantime::parse_duration(&format!("{s}s")).map(HumanDuration);
+        }
+        humantime::parse_duration(s).map(HumanDuration)
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(HumanDurationVisitor {
+            from_number: Duration::from_secs,
+            unit: "seconds",
+        })
+    }
+}
+
+/// Deserializes a duration as [`HumanDuration`] does, but taking a number on its own as milliseconds, for fields that
+/// were given in milliseconds before units could be used.
+///
+/// # Arguments
+/// * `deserializer`: The deserializer.
+///
+/// # Returns
+/// The duration.
+pub fn deserialize_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    deserializer
+        .deserialize_any(HumanDurationVisitor {
+            from_number: Duration::from_millis,
+            unit: "milliseconds",
+        })
+        .map(|HumanDuration(duration)| duration)
+}
+
+/// Visitor for [`HumanDuration`], taking a string or a number in some unit.
+struct HumanDurationVisitor {
+    /// Makes a duration from a number on its own.
+    from_number: fn(u64) -> Duration,
+    /// Name of the unit numbers on their own are in, for errors.
+    unit: &'static str,
+}
+
+impl<'de> de::Visitor<'de> for HumanDurationVisitor {
+    type Value = HumanDuration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a duration such as `90s` or `1m 30s`, or a number of {}",
+            self.unit
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, number: u64) -> Result<Self::Value, E> {
+        Ok(HumanDuration((self.from_number)(number)))
+    }
+
+    // TOML integers are signed.
+    fn visit_i64<E: de::Error>(self, number: i64) -> Result<Self::Value, E> {
+        let number = u64::try_from(number)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(number), &self))?;
+        self.visit_u64(number)
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        if let Ok(number) = s.trim().parse() {
+            return self.visit_u64(number);
+        }
+        s.parse()
+            .map_err(|e| E::custom(format!("invalid duration `{s}`: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Duration, humantime::DurationError> {
+        s.parse().map(|HumanDuration(duration)| duration)
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("1500ms"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+    }
+
+    #[test]
+    fn test_parse_bare_seconds() {
+        assert_eq!(parse("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse(" 30 "), Ok(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_parse_compound() {
+        assert_eq!(parse("1m 30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("1h30m"), Ok(Duration::from_secs(90 * 60)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("soon").is_err());
+        assert!(parse("5 parsecs").is_err());
+        assert!(parse("-5s").is_err());
+    }
+
+    #[derive(Deserialize)]
+    struct Durations {
+        seconds: HumanDuration,
+        #[serde(deserialize_with = "deserialize_millis")]
+        millis: Duration,
+    }
+
+    #[test]
+    fn test_deserialize_bare_numbers() {
+        let durations: Durations = toml::from_str("seconds = 5\nmillis = 300").unwrap();
+        assert_eq!(durations.seconds, HumanDuration(Duration::from_secs(5)));
+        assert_eq!(durations.millis, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_deserialize_units() {
+        let durations: Durations =
+            toml::from_str("seconds = \"1m 30s\"\nmillis = \"1.5s\"").unwrap();
+        assert_eq!(durations.seconds, HumanDuration(Duration::from_secs(90)));
+        assert_eq!(durations.millis, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        assert!(toml::from_str::<Durations>("seconds = -5\nmillis = 300").is_err());
+        assert!(toml::from_str::<Durations>("seconds = \"soon\"\nmillis = 300").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{config::ConfigError, display::DisplayError};
+
+/// Errors that stop the service from starting or running.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+    #[cfg(feature = "serial")]
+    #[error("could not open serial port {path}: {source}")]
+    SerialPort {
+        path: String,
+        source: serialport::Error,
+    },
+    #[cfg(not(feature = "serial"))]
+    #[error("built without serial support, use --tcp to connect to the sign over the network")]
+    NoSerialSupport,
+    #[error("could not connect to {address}: {source}")]
+    Tcp {
+        address: String,
+        source: std::io::Error,
+    },
+    #[cfg(feature = "serial")]
+    #[error("sign on {0} did not respond to any of the probed serial settings")]
+    NoResponse(String),
+    #[error("could not open recording file {path}: {source}", path = .path.display())]
+    Recording {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{0}")]
+    Display(#[from] DisplayError),
+    #[error("no memory layout in the config file, set `memory` to reprovision the sign")]
+    NoMemoryLayout,
+    #[error("HTTP server error: {0}")]
+    Http(Box<dyn std::error::Error + Send + Sync>),
+}
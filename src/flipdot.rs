@@ -0,0 +1,334 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{
+    display::{BannerPicture, DisplayBackend, DisplayError},
+    transport::Connection,
+};
+use alpha_sign::{
+    dots::{Bitmap, DotColor, WriteSmallDotsPicture},
+    text::{ReadText, WriteText},
+    SignError,
+};
+
+/// Width of a glyph in [`FONT`], in dots.
+const GLYPH_WIDTH: usize = 5;
+
+/// Height of a glyph in [`FONT`], in dots.
+const GLYPH_HEIGHT: usize = 7;
+
+/// 5x7 font covering printable ASCII (0x20 to 0x7E). Each glyph is five columns, with the top dot in the lowest bit.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x02, 0x01, 0x02, 0x04, 0x02], // ~
+];
+
+/// Renders text into columns of dots with [`FONT`], with a blank column between characters.
+///
+/// # Arguments
+/// * `text`: The text to render. Characters outside printable ASCII are shown as `?`.
+///
+/// # Returns
+/// One entry per column, with the top dot in the lowest bit.
+fn glyph_columns(text: &str) -> Vec<u8> {
+    let mut dots: Vec<u8> = vec![];
+    for c in text.chars() {
+        let glyph = match c {
+            ' '..='~' => &FONT[c as usize - 0x20],
+            _ => &FONT['?' as usize - 0x20],
+        };
+        dots.extend(glyph);
+        dots.push(0);
+    }
+    dots.pop(); // remove trailing gap
+    dots
+}
+
+/// Draws text in big characters into a SMALL DOTS PICTURE, scaling [`FONT`] up by as much as fits in the picture's
+/// height.
+///
+/// # Arguments
+/// * `picture`: The picture file to draw into.
+/// * `text`: The text to draw.
+///
+/// # Returns
+/// The command writing the picture, with the characters in red, or an error if it would be more than 255 dots wide.
+pub fn banner(picture: BannerPicture, text: &str) -> Result<WriteSmallDotsPicture, SignError> {
+    let scale = picture
+        .height
+        .map_or(1, |height| (usize::from(height) / GLYPH_HEIGHT).max(1));
+    let columns = glyph_columns(text);
+    let rows = (0..GLYPH_HEIGHT * scale)
+        .map(|y| {
+            columns
+                .iter()
+                .flat_map(|column| {
+                    let dot = if column >> (y / scale) & 1 == 1 {
+                        DotColor::Red
+                    } else {
+                        DotColor::Off
+                    };
+                    std::iter::repeat_n(dot, scale)
+                })
+                .collect()
+        })
+        .collect();
+    WriteSmallDotsPicture::new(picture.label, Bitmap::new(rows)?)
+}
+
+/// A flipdot (or LED matrix) panel behind a generic serial controller.
+///
+/// Each frame sent to the controller is `0x80 0x83 <address> <columns...> 0x8F`. Columns are sent left to right, each
+/// as `ceil(height / 7)` bytes holding seven dots apiece from the top down, with the top dot of each byte in the
+/// lowest bit. Keeping the high bit of data bytes clear means they can never be mistaken for the start or end bytes.
+pub struct FlipdotBackend {
+    port: Box<dyn Connection>,
+    address: u8,
+    width: usize,
+    height: usize,
+    files: HashMap<char, String>,
+}
+
+impl FlipdotBackend {
+    const FRAME_START: [u8; 2] = [0x80, 0x83];
+    const FRAME_END: u8 = 0x8F;
+
+    /// Creates a new [`FlipdotBackend`].
+    ///
+    /// # Arguments
+    /// * `port`: The connection to the controller.
+    /// * `address`: Address of the panel on the controller's bus.
+    /// * `width`: Width of the panel, in dots.
+    /// * `height`: Height of the panel, in dots.
+    ///
+    /// # Returns
+    /// A new [`FlipdotBackend`].
+    pub fn new(port: Box<dyn Connection>, address: u8, width: usize, height: usize) -> Self {
+        Self {
+            port,
+            address,
+            width,
+            height,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Renders text into columns of dots, centred on the panel. Text that doesn't fit is cut off on the right.
+    ///
+    /// # Arguments
+    /// * `text`: The text to render.
+    ///
+    /// # Returns
+    /// One entry per column of the panel, with the top dot in the lowest bit.
+    fn render(&self, text: &str) -> Vec<u64> {
+        let mut dots: Vec<u64> = glyph_columns(text).into_iter().map(u64::from).collect();
+        dots.truncate(self.width);
+
+        let left = (self.width - dots.len()) / 2;
+        let top = self.height.saturating_sub(GLYPH_HEIGHT) / 2;
+        let mut columns = vec![0; self.width];
+        for (column, dots) in columns[left..].iter_mut().zip(dots) {
+            *column = dots << top;
+        }
+        columns
+    }
+
+    /// Builds the frame that shows the given columns of dots.
+    ///
+    /// # Arguments
+    /// * `columns`: Columns of dots, as returned by [`FlipdotBackend::render`].
+    ///
+    /// # Returns
+    /// The bytes to send to the controller.
+    fn encode(&self, columns: &[u64]) -> Vec<u8> {
+        let mut res: Vec<u8> = Self::FRAME_START.into();
+        res.push(self.address);
+        for column in columns {
+            for chunk in 0..self.height.div_ceil(7) {
+                res.push(((column >> (chunk * 7)) & 0x7F) as u8);
+            }
+        }
+        res.push(Self::FRAME_END);
+        res
+    }
+}
+
+impl DisplayBackend for FlipdotBackend {
+    fn write_text(&mut self, text: WriteText) -> Result<(), DisplayError> {
+        let frame = self.encode(&self.render(&text.message));
+        self.port.write_all(frame.as_slice())?;
+        self.files.insert(text.label, text.message);
+        Ok(())
+    }
+
+    fn read_text(&mut self, read: ReadText) -> Result<String, DisplayError> {
+        // The controller can't be read from, so return what was last written instead.
+        Ok(self.files.get(&read.label).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::FakeConnection;
+
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let connection = FakeConnection::default();
+        let mut flipdot = FlipdotBackend::new(Box::new(connection.clone()), 5, 12, 9);
+        flipdot
+            .write_text(WriteText::new('A', "I".to_string()))
+            .unwrap();
+
+        // "I" is five columns wide, so it starts three columns in, and is one dot down. Nine dots high takes two bytes
+        // per column, the second holding the dots below the top seven.
+        let mut expected = vec![0x80, 0x83, 5];
+        expected.extend([0x00, 0x00].repeat(4));
+        expected.extend([0x02, 0x01, 0x7E, 0x01, 0x02, 0x01]);
+        expected.extend([0x00, 0x00].repeat(5));
+        expected.push(0x8F);
+        let written = connection.written.lock().unwrap();
+        assert_eq!(*written, expected);
+        assert!(written[3..written.len() - 1].iter().all(|&b| b < 0x80));
+    }
+
+    #[test]
+    fn test_banner() {
+        let picture = BannerPicture {
+            label: 'Z',
+            height: Some(16),
+        };
+        let banner = banner(picture, "I").unwrap();
+        assert_eq!(banner.label, 'Z');
+
+        // Scaled up twice over to fit 16 dots, the top row of "I" is three dots across the middle of five, each two
+        // dots wide and two high
+        let rows = banner.bitmap.rows();
+        assert_eq!((rows.len(), rows[0].len()), (14, 10));
+        let top: String = rows[0]
+            .iter()
+            .map(|dot| if *dot == DotColor::Red { '#' } else { '.' })
+            .collect();
+        assert_eq!(top, "..######..");
+        assert_eq!(rows[0], rows[1]);
+    }
+
+    #[test]
+    fn test_banner_too_wide() {
+        let picture = BannerPicture {
+            label: 'Z',
+            height: Some(32),
+        };
+        assert!(banner(picture, "CLOSED FOR THE DAY").is_err());
+    }
+
+    #[test]
+    fn test_text_cut_off() {
+        let connection = FakeConnection::default();
+        let mut flipdot = FlipdotBackend::new(Box::new(connection.clone()), 0, 7, 7);
+        flipdot
+            .write_text(WriteText::new('A', "HI".to_string()))
+            .unwrap();
+
+        // The gap and the start of "I" don't fit
+        let written = connection.written.lock().unwrap();
+        assert_eq!(
+            written[3..written.len() - 1],
+            [0x7F, 0x08, 0x08, 0x08, 0x7F, 0x00, 0x00]
+        );
+    }
+}
@@ -1,77 +1,543 @@
+mod api_recording;
+mod config;
+#[cfg(feature = "serial")]
+mod discover;
+mod display;
+#[cfg(feature = "serial")]
+mod doctor;
+mod duration;
+mod error;
+mod flipdot;
+mod markdown;
+mod recording;
+mod serial;
+mod transport;
 mod web_server;
 
+use crate::api_recording::ApiRecorder;
+use crate::config::{Config, StartupStep};
+use crate::display::{
+    clock_drift, AlphaBackend, Backend, BannerPicture, DisplayBackend, MirroredBackend,
+    TerminalBackend,
+};
+use crate::duration::HumanDuration;
+use crate::error::Error;
+use crate::flipdot::FlipdotBackend;
+use crate::recording::Recorder;
+use crate::serial::{Baudrate, DataBits, FailoverStatus, Pacer, Parity, StopBits};
+#[cfg(feature = "serial")]
+use crate::serial::{FailoverPort, Framing, Reopen};
+use crate::transport::Connection;
 use crate::web_server::{app, AppState};
-use alpha_sign::text::WriteText;
-use alpha_sign::Command;
-use alpha_sign::Packet;
-use alpha_sign::SignSelector;
+use alpha_sign::text::{call_time, TransitionMode, WriteText};
+use alpha_sign::{ProtocolVersion, SignSelector};
 use clap::Parser;
-// use rhai::EvalAltResult;
+use rand::seq::SliceRandom;
+#[cfg(feature = "serial")]
 use serialport::SerialPort;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::{
-    net::{Ipv4Addr, SocketAddr},
-    //    thread,
-    time::Duration,
-};
+// use rhai::EvalAltResult;
+use sd_notify::NotifyState;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::os::fd::FromRawFd;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
 use tokio::select;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
 use tokio_util::sync::CancellationToken;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
-use web_server::APICommand;
+use tracing::Instrument;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer};
+use web_server::{APICommand, DisplayedText, LogFilterHandle, QueuedCommand};
+
+/// How often to check whether the quiet hours have started or ended.
+const NIGHT_MODE_CHECK: Duration = Duration::from_secs(30);
 
 /// Service for communicating with the YHS sign.
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+    // kind of display to drive, can be given more than once to show the same text on several displays
+    #[arg(long, value_enum, default_value = "alpha")]
+    backend: Vec<Backend>,
     // serial port to use to connect to the sign
     #[arg(long, default_value = "/dev/ttyUSB0")]
     port: String,
-    // baud rate to use for the port
+    // version of the Alpha protocol the sign speaks (1, 2 or 3) if it doesn't say when asked, BetaBrites and older
+    // signs only speak 1
+    #[arg(long, default_value = "3")]
+    protocol: ProtocolVersion,
+    // backup serial port to switch to if the sign's port keeps failing, e.g. a second USB serial adapter
+    #[arg(long)]
+    backup_port: Option<String>,
+    // address (host:port) of a serial-to-network adapter to connect to the sign through instead of a serial port
+    #[arg(long)]
+    tcp: Option<String>,
+    // baud rate to use for the port, or "auto" to try each supported rate until the sign responds
     #[arg(long, default_value = "9600")]
-    baudrate: u32,
+    baudrate: Baudrate,
+    // number of data bits per character
+    #[arg(long, value_enum, default_value = "8")]
+    data_bits: DataBits,
+    // parity bit mode
+    #[arg(long, value_enum, default_value = "none")]
+    parity: Parity,
+    // number of stop bits per character
+    #[arg(long, value_enum, default_value = "1")]
+    stop_bits: StopBits,
+    // ignore the framing options and instead try common framings (8N1, 7E1, 7E2, 7O1) until the sign responds
+    #[arg(long)]
+    probe_framing: bool,
+    // serial port the flipdot controller is connected to
+    #[arg(long, default_value = "/dev/ttyUSB1")]
+    flipdot_port: String,
+    // baud rate to use for the flipdot controller
+    #[arg(long, default_value = "57600")]
+    flipdot_baudrate: u32,
+    // address of the flipdot panel on the controller's bus
+    #[arg(long, default_value = "0")]
+    flipdot_address: u8,
+    // width of the flipdot panel in dots
+    #[arg(long, default_value = "28")]
+    flipdot_width: usize,
+    // height of the flipdot panel in dots
+    #[arg(long, default_value = "7", value_parser = clap::value_parser!(u8).range(1..=64))]
+    flipdot_height: u8,
+    // config file to load settings from, re-read when the process receives a SIGHUP
+    #[arg(long)]
+    config: Option<PathBuf>,
+    // file to record all serial traffic with the sign to, for replaying later
+    #[arg(long)]
+    record: Option<PathBuf>,
+    // file to record every HTTP API request and its response to, as fixtures for the API regression tests
+    #[arg(long)]
+    record_api: Option<PathBuf>,
+    // seconds to wait between attempts to open the displays if they can't be opened at startup
+    #[arg(long, default_value = "5")]
+    connect_retry_seconds: u64,
+}
+
+/// Things to do other than running the service.
+#[derive(clap::Subcommand, Clone, Debug)]
+enum Subcommand {
+    /// Decode a recording made with `--record` and print each packet in it.
+    Replay {
+        /// The recording to decode.
+        file: PathBuf,
+    },
+    /// Lay out the sign's memory as set by `memory` in the config file, keeping the text in files that already exist.
+    Reprovision,
+    /// Check the serial port, sign and HTTP service are working and suggest fixes for anything that isn't.
+    #[cfg(feature = "serial")]
+    Doctor {
+        /// Port the HTTP API is expected to be listening on.
+        #[arg(long, default_value = "8080")]
+        http_port: u16,
+    },
+    /// Scan every address on the serial port and list the signs that respond, for finding a sign whose address is
+    /// unknown.
+    #[cfg(feature = "serial")]
+    Discover,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    match &args.command {
+        Some(Subcommand::Replay { file }) => {
+            if let Err(e) = recording::replay(file) {
+                eprintln!("Failed to replay {}: {e}", file.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        #[cfg(feature = "serial")]
+        Some(Subcommand::Doctor { http_port }) => {
+            let framings = if args.probe_framing {
+                Framing::COMMON.to_vec()
+            } else {
+                vec![Framing::new(args.data_bits, args.parity, args.stop_bits)]
+            };
+            let config = match &args.config {
+                Some(path) => match Config::load(path) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(1);
+                    }
+                },
+                None => Config::default(),
+            };
+            let healthy = doctor::run(
+                &args.port,
+                args.baudrate,
+                &framings,
+                SignSelector::default(),
+                &config.memory,
+                *http_port,
+            );
+            std::process::exit(if healthy { 0 } else { 1 });
+        }
+        #[cfg(feature = "serial")]
+        Some(Subcommand::Discover) => {
+            let mut port = match open_serial_device(&args, &args.port, SignSelector::default()) {
+                Ok((port, _, _)) => port,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            let found = discover::run(&mut port);
+            std::process::exit(if found.is_empty() { 1 } else { 0 });
+        }
+        Some(Subcommand::Reprovision) | None => {}
+    }
+
     dotenv::dotenv().ok();
-    init_logging();
+    let log_filter = init_logging();
 
     tracing::info!("🦊 Hello YHS! 🦊");
 
-    let mut port: Box<dyn SerialPort> = serialport::new(args.port.as_str(), args.baudrate)
-        .timeout(Duration::from_millis(1000))
-        .parity(serialport::Parity::None)
-        .data_bits(serialport::DataBits::Eight)
-        .stop_bits(serialport::StopBits::One)
-        .open()
-        .expect("Failed to open port");
+    let res = match args.command {
+        Some(Subcommand::Reprovision) => reprovision(&args),
+        _ => run(&args, log_filter).await,
+    };
+    if let Err(e) = res {
+        tracing::error!("{e}");
+        std::process::exit(1);
+    }
+}
 
-    let yhs_selector = SignSelector::default();
-    // yhs_selector.checksum = false;
+/// Lays out the sign's memory as set in the config file.
+///
+/// # Arguments
+/// * `args`: Command line arguments.
+fn reprovision(args: &Args) -> Result<(), Error> {
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    if config.memory.is_empty() {
+        return Err(Error::NoMemoryLayout);
+    }
+
+    let sign = SignSelector::default();
+    let (port, pacer) = open_sign_port(args, sign, &Arc::default())?;
+    let mut alpha = AlphaBackend::new(sign, port).protocol(args.protocol);
+    if let Some(pacer) = pacer {
+        alpha = alpha.pacer(pacer);
+    }
+    alpha.configure_memory(&config.memory, config.keyboard_access)?;
+    tracing::info!(
+        "Reprovisioned sign memory with {} files",
+        config.memory.len()
+    );
+    Ok(())
+}
+
+/// Opens the displays and runs the service until it is stopped or fails.
+///
+/// # Arguments
+/// * `args`: Command line arguments.
+/// * `log_filter`: Handle for changing which logs are shown.
+async fn run(args: &Args, log_filter: LogFilterHandle) -> Result<(), Error> {
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let (config_tx, config_rx) = watch::channel(config);
+    if let Some(path) = args.config.clone() {
+        tokio::spawn(reload_config_on_sighup(path, config_tx));
+    }
 
     let (sign_command_tx, sign_command_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(night_mode_on_schedule(
+        config_rx.clone(),
+        sign_command_tx.clone(),
+    ));
+    let (clock_drift_tx, clock_drift_rx) = watch::channel(None);
+    tokio::spawn(check_clock_on_schedule(
+        config_rx.clone(),
+        sign_command_tx.clone(),
+        clock_drift_tx,
+    ));
 
     let cancel_sign = CancellationToken::new();
     let cancel_sign_task = cancel_sign.clone();
 
-    let app_state = web_server::AppState::new(sign_command_tx);
+    let (displayed_tx, displayed_rx) = watch::channel(None);
+    let (connected_tx, connected_rx) = watch::channel(false);
 
-    let message_loop = talk_to_sign(yhs_selector, port, sign_command_rx, cancel_sign_task);
-    let http_api = serve_api(app_state, 8080);
+    let failover = Arc::new(FailoverStatus::default());
 
-    select! {
-        _ = message_loop => {},
-        _ = http_api => {},
-    }
+    let startup = config_rx.borrow().startup.clone();
+    let app_state = web_server::AppState::new(
+        sign_command_tx,
+        config_rx,
+        displayed_rx,
+        connected_rx,
+        args.backup_port.as_ref().map(|_| failover.clone()),
+        clock_drift_rx,
+        log_filter,
+    );
+
+    // The HTTP API comes up straight away, and commands sent before the displays open wait in the channel.
+    let message_loop = async {
+        let Some(display) = connect_displays(args, &failover, &cancel_sign_task).await else {
+            return;
+        };
+        connected_tx.send_replace(true);
+        talk_to_sign(
+            display,
+            startup,
+            sign_command_rx,
+            displayed_tx,
+            cancel_sign_task,
+        )
+        .await
+    };
+    let api_recorder = match &args.record_api {
+        Some(path) => Some(
+            ApiRecorder::create(path).map_err(|source| Error::Recording {
+                path: path.clone(),
+                source,
+            })?,
+        ),
+        None => None,
+    };
+    let http_api = serve_api(app_state, api_recorder, 8080);
+
+    let res = select! {
+        _ = message_loop => Ok(()),
+        res = http_api => res,
+    };
 
     cancel_sign.cancel();
+    res
+}
+
+/// Opens the displays, retrying in the background until they open so the HTTP API can run meanwhile.
+///
+/// # Arguments
+/// * `args`: Command line arguments.
+/// * `failover`: Where to report which serial device is in use, if there is a backup one.
+/// * `cancel`: Token that stops the retries when the service is stopped.
+///
+/// # Returns
+/// The opened displays, or [`None`] if the service was stopped first.
+async fn connect_displays(
+    args: &Args,
+    failover: &Arc<FailoverStatus>,
+    cancel: &CancellationToken,
+) -> Option<MirroredBackend> {
+    loop {
+        // Opening serial ports and probing for settings blocks, so keep it off the async workers.
+        let attempt_args = args.clone();
+        let attempt_failover = failover.clone();
+        match tokio::task::spawn_blocking(move || open_displays(&attempt_args, &attempt_failover))
+            .await
+        {
+            Ok(Ok(display)) => return Some(display),
+            Ok(Err(e)) => {
+                tracing::error!("{e}, retrying in {} seconds", args.connect_retry_seconds)
+            }
+            Err(e) => tracing::error!("Opening displays panicked: {e}"),
+        }
+        select! {
+            _ = cancel.cancelled() => return None,
+            _ = tokio::time::sleep(Duration::from_secs(args.connect_retry_seconds)) => {},
+        }
+    }
+}
+
+/// Opens each display chosen with `--backend`.
+///
+/// # Arguments
+/// * `args`: Command line arguments.
+/// * `failover`: Where to report which serial device the sign is on, if there is a backup one.
+///
+/// # Returns
+/// The opened displays.
+fn open_displays(args: &Args, failover: &Arc<FailoverStatus>) -> Result<MirroredBackend, Error> {
+    let yhs_selector = SignSelector::default();
+    // yhs_selector.checksum = false;
+
+    let mut displays: Vec<(Backend, Box<dyn DisplayBackend>)> = vec![];
+    for &backend in &args.backend {
+        let display: Box<dyn DisplayBackend> = match backend {
+            Backend::Alpha => {
+                let (port, pacer) = open_sign_port(args, yhs_selector, failover)?;
+                let mut alpha = AlphaBackend::new(yhs_selector, port);
+                if let Some(pacer) = pacer {
+                    alpha = alpha.pacer(pacer);
+                }
+                if let Some(path) = &args.record {
+                    let recorder = Recorder::create(path).map_err(|source| Error::Recording {
+                        path: path.clone(),
+                        source,
+                    })?;
+                    alpha = alpha.recorder(recorder);
+                }
+                Box::new(alpha.negotiate(args.protocol))
+            }
+            Backend::Terminal => Box::new(TerminalBackend::new()),
+            Backend::Flipdot => Box::new(FlipdotBackend::new(
+                open_flipdot_port(args)?,
+                args.flipdot_address,
+                args.flipdot_width,
+                args.flipdot_height.into(),
+            )),
+        };
+        displays.push((backend, display));
+    }
+    Ok(MirroredBackend::new(displays))
+}
+
+/// Opens the connection to the sign, over TCP if an address was given and otherwise over the serial port, probing for
+/// working settings if asked to.
+///
+/// # Arguments
+/// * `args`: Command line arguments with the connection settings to use.
+/// * `sign`: The sign to address when probing.
+/// * `failover`: Where to report which serial device is in use, if there is a backup one.
+///
+/// # Returns
+/// The opened connection, and a pacer to suit it if it's a serial port. Network adapters do their own buffering.
+fn open_sign_port(
+    args: &Args,
+    sign: SignSelector,
+    failover: &Arc<FailoverStatus>,
+) -> Result<(Box<dyn Connection>, Option<Pacer>), Error> {
+    if let Some(address) = &args.tcp {
+        let connection = transport::connect_tcp(address).map_err(|source| Error::Tcp {
+            address: address.clone(),
+            source,
+        })?;
+        return Ok((connection, None));
+    }
+    open_sign_serial_port(args, sign, failover)
+}
+
+#[cfg(feature = "serial")]
+fn open_sign_serial_port(
+    args: &Args,
+    sign: SignSelector,
+    failover: &Arc<FailoverStatus>,
+) -> Result<(Box<dyn Connection>, Option<Pacer>), Error> {
+    let Some(backup) = &args.backup_port else {
+        let (port, baudrate, framing) = open_serial_device(args, &args.port, sign)?;
+        return Ok((Box::new(port), Some(Pacer::new(baudrate, framing))));
+    };
+
+    let (port, active, baudrate, framing) = match open_serial_device(args, &args.port, sign) {
+        Ok((port, baudrate, framing)) => (port, 0, baudrate, framing),
+        Err(e) => {
+            tracing::warn!("{e}, trying backup port {backup}");
+            let (port, baudrate, framing) = open_serial_device(args, backup, sign)?;
+            (port, 1, baudrate, framing)
+        }
+    };
+    let reopen: Reopen = Box::new(move |path| {
+        let port = serial::open(path, baudrate, framing)?;
+        Ok(Box::new(port) as Box<dyn Connection>)
+    });
+    let port = FailoverPort::new(
+        [args.port.clone(), backup.clone()],
+        active,
+        Box::new(port),
+        reopen,
+        failover.clone(),
+    );
+    Ok((Box::new(port), Some(Pacer::new(baudrate, framing))))
+}
+
+/// Opens a serial device the sign might be connected to, probing for working settings if asked to.
+///
+/// # Arguments
+/// * `args`: Command line arguments with the serial settings to use.
+/// * `path`: Path of the serial device.
+/// * `sign`: The sign to address when probing.
+///
+/// # Returns
+/// The opened port, along with the baud rate and framing it was opened with.
+#[cfg(feature = "serial")]
+fn open_serial_device(
+    args: &Args,
+    path: &str,
+    sign: SignSelector,
+) -> Result<(Box<dyn SerialPort>, u32, Framing), Error> {
+    match (args.baudrate, args.probe_framing) {
+        (Baudrate::Fixed(baudrate), false) => {
+            let framing = Framing::new(args.data_bits, args.parity, args.stop_bits);
+            let port =
+                serial::open(path, baudrate, framing).map_err(|source| Error::SerialPort {
+                    path: path.to_string(),
+                    source,
+                })?;
+            Ok((port, baudrate, framing))
+        }
+        (baudrate, probe_framing) => {
+            let baudrates = match baudrate {
+                Baudrate::Auto => Baudrate::SUPPORTED.to_vec(),
+                Baudrate::Fixed(baudrate) => vec![baudrate],
+            };
+            let framings = if probe_framing {
+                Framing::COMMON.to_vec()
+            } else {
+                vec![Framing::new(args.data_bits, args.parity, args.stop_bits)]
+            };
+            let (port, baudrate, framing) = serial::probe(path, &baudrates, &framings, sign)
+                .ok_or_else(|| Error::NoResponse(path.to_string()))?;
+            tracing::info!("Sign responded on {path} at {baudrate} baud using {framing} framing");
+            Ok((port, baudrate, framing))
+        }
+    }
+}
+
+#[cfg(not(feature = "serial"))]
+fn open_sign_serial_port(
+    _args: &Args,
+    _sign: SignSelector,
+    _failover: &Arc<FailoverStatus>,
+) -> Result<(Box<dyn Connection>, Option<Pacer>), Error> {
+    Err(Error::NoSerialSupport)
+}
+
+/// Opens the serial port to the flipdot controller.
+///
+/// # Arguments
+/// * `args`: Command line arguments with the flipdot settings to use.
+///
+/// # Returns
+/// The opened port.
+#[cfg(feature = "serial")]
+fn open_flipdot_port(args: &Args) -> Result<Box<dyn Connection>, Error> {
+    let port = serial::open(
+        &args.flipdot_port,
+        args.flipdot_baudrate,
+        Framing::new(DataBits::Eight, Parity::None, StopBits::One),
+    )
+    .map_err(|source| Error::SerialPort {
+        path: args.flipdot_port.clone(),
+        source,
+    })?;
+    Ok(Box::new(port))
+}
+
+#[cfg(not(feature = "serial"))]
+fn open_flipdot_port(_args: &Args) -> Result<Box<dyn Connection>, Error> {
+    Err(Error::NoSerialSupport)
 }
 
-/// Set up logging.
-fn init_logging() {
+/// Sets up logging to stdout, filtered by `RUST_LOG`.
+///
+/// # Returns
+/// A handle for changing the filter while the service is running.
+fn init_logging() -> LogFilterHandle {
     #[cfg(debug_assertions)]
     if std::env::var("RUST_LIB_BACKTRACE").is_err() {
         std::env::set_var("RUST_LIB_BACKTRACE", "1")
@@ -82,31 +548,175 @@ fn init_logging() {
     }
 
     let stdout_log = tracing_subscriber::fmt::layer().compact();
-    let env_filter = EnvFilter::from_default_env();
+    let (env_filter, log_filter) = reload::Layer::new(EnvFilter::from_default_env());
     tracing_subscriber::registry()
         .with(stdout_log.with_filter(env_filter))
         .init();
+    log_filter
+}
+
+/// Reloads the config file whenever the process receives a SIGHUP. If the new config can't be loaded the current one
+/// is kept.
+///
+/// # Arguments
+/// * `path`: Path of the config file.
+/// * `config_tx`: Channel the reloaded config is published to.
+async fn reload_config_on_sighup(path: PathBuf, config_tx: watch::Sender<Config>) {
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGHUP, config will not be reloadable: {e}");
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        sd_notify::notify(false, &[NotifyState::Reloading]).ok();
+        match Config::load(&path) {
+            Ok(config) => {
+                tracing::info!("Reloaded config from {}", path.display());
+                config_tx.send_replace(config);
+            }
+            Err(e) => tracing::error!("Keeping current config, {e}"),
+        }
+        sd_notify::notify(false, &[NotifyState::Ready]).ok();
+    }
+}
+
+/// Switches night mode on and off at the start and end of the quiet hours set in the config, checking every
+/// [`NIGHT_MODE_CHECK`]. Quiet hours are in local time at the configured UTC offset. Night mode is only changed when
+/// the quiet hours start or end, so switching it by hand with the API lasts until then.
+///
+/// # Arguments
+/// * `config_rx`: Receiver for the current config.
+/// * `command_tx`: Channel night mode commands are sent to the sign loop through.
+async fn night_mode_on_schedule(
+    config_rx: watch::Receiver<Config>,
+    command_tx: tokio::sync::mpsc::UnboundedSender<QueuedCommand>,
+) {
+    let mut quiet = false;
+    let mut checks = tokio::time::interval(NIGHT_MODE_CHECK);
+    loop {
+        checks.tick().await;
+        let now = OffsetDateTime::now_utc();
+        let (now_quiet, dimming) = {
+            let night = &config_rx.borrow().night;
+            let now_quiet = night.is_quiet(night.local_time(now)).unwrap_or(false);
+            (now_quiet, night.dimming_for(now_quiet))
+        };
+        if now_quiet == quiet {
+            continue;
+        }
+        quiet = now_quiet;
+        tracing::info!("Quiet hours {}", if quiet { "started" } else { "ended" });
+        let command = QueuedCommand {
+            request_id: None,
+            command: APICommand::NightMode(quiet, dimming),
+        };
+        if command_tx.send(command).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads the sign's clock as often as set in the config and compares it with the host's, publishing how far it has
+/// drifted and logging a warning when that's more than the config allows.
+///
+/// # Arguments
+/// * `config_rx`: Receiver for the current config.
+/// * `command_tx`: Channel clock reads are sent to the sign loop through.
+/// * `clock_drift_tx`: Channel the sign clock's drift, in minutes, is published to.
+async fn check_clock_on_schedule(
+    mut config_rx: watch::Receiver<Config>,
+    command_tx: tokio::sync::mpsc::UnboundedSender<QueuedCommand>,
+    clock_drift_tx: watch::Sender<Option<i64>>,
+) {
+    loop {
+        let every = config_rx.borrow().clock.every;
+        let Some(HumanDuration(every)) = every else {
+            // Not checked until a reloaded config asks for it
+            if config_rx.changed().await.is_err() {
+                return;
+            }
+            continue;
+        };
+        tokio::time::sleep(every).await;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let command = QueuedCommand {
+            request_id: None,
+            command: APICommand::ReadTime(tx),
+        };
+        if command_tx.send(command).is_err() {
+            return;
+        }
+        // Failed reads are logged by the sign loop
+        let Ok(web_server::APIResponse::Time(sign_time)) = rx.await else {
+            continue;
+        };
+        let (host_time, max_drift) = {
+            let config = config_rx.borrow();
+            (
+                config.night.local_time(OffsetDateTime::now_utc()),
+                config.clock.max_drift.0,
+            )
+        };
+        let drift = clock_drift(sign_time, host_time);
+        clock_drift_tx.send_replace(Some(drift));
+        if drift.unsigned_abs() * 60 > max_drift.as_secs() {
+            tracing::warn!(
+                "Sign's clock is {} minutes {}, showing {sign_time} at {host_time}",
+                drift.unsigned_abs(),
+                if drift > 0 { "fast" } else { "slow" }
+            );
+        } else {
+            tracing::debug!("Sign's clock is {drift} minutes out");
+        }
+    }
 }
 
 /// Enters a loop of communicating with the sign and handling commands sent into the message channel.
 ///
 /// # Arguments
-/// * `sign`: The sign to talk to.
+/// * `display`: The displays to talk to.
+/// * `startup`: Sequence of text to show before handling any commands.
 /// * `message_rx`: Receiver for commands to be handled.
+/// * `displayed_tx`: Channel the text currently shown on the sign is published to.
 /// * `cancel`: [`CancellationToken`] that can be used to stop the task from running.
 async fn talk_to_sign(
-    sign: SignSelector,
-    mut port: Box<dyn SerialPort>,
-    mut message_rx: tokio::sync::mpsc::UnboundedReceiver<APICommand>,
+    mut display: MirroredBackend,
+    startup: Vec<StartupStep>,
+    mut message_rx: tokio::sync::mpsc::UnboundedReceiver<QueuedCommand>,
+    displayed_tx: watch::Sender<Option<DisplayedText>>,
     cancel: CancellationToken,
 ) {
+    // Commands sent while the startup sequence is showing wait in the channel until it has finished.
+    for step in startup {
+        let text = WriteText::new('A', step.text);
+        handle_command(
+            &mut display,
+            &displayed_tx,
+            APICommand::WriteText(text, None),
+        )
+        .await;
+        select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(step.duration.0) => {},
+        }
+    }
+
     while !cancel.is_cancelled() {
         select! {
             _ = cancel.cancelled() => {},
             message = message_rx.recv() => {
                 match message {
-                    Some(command) => {
-                        handle_command(sign, &mut port, command).await;
+                    Some(QueuedCommand { request_id, command }) => {
+                        // Log everything done for the command, down to the packets sent to the sign, under the ID of
+                        // the request that sent it.
+                        let span = tracing::info_span!("command", request_id);
+                        handle_command(&mut display, &displayed_tx, command)
+                            .instrument(span)
+                            .await;
                     }
                     None => {
                         tracing::debug!(
@@ -123,49 +733,219 @@ async fn talk_to_sign(
 /// Handle a [`APICommand`]
 ///
 /// # Arguments
-/// * `sign`: The sign to send commands to.
-/// * `port`: the serial port to send things down
+/// * `display`: The displays to send commands to.
+/// * `displayed_tx`: Channel the text currently shown on the sign is published to.
 /// * `command`: The command to handle.
-async fn handle_command(sign: SignSelector, port: &mut Box<dyn SerialPort>, command: APICommand) {
+async fn handle_command(
+    display: &mut MirroredBackend,
+    displayed_tx: &watch::Sender<Option<DisplayedText>>,
+    command: APICommand,
+) {
     match command {
-        APICommand::WriteText(text) => {
-            let write_text_command = Packet::new(vec![sign], vec![Command::WriteText(text)])
-                .encode()
-                .unwrap();
-
-            port.write(write_text_command.as_slice()).ok(); // TODO handle errors
+        APICommand::WriteText(text, targets) => {
+            write_text(display, displayed_tx, text, targets, None);
+        }
+        APICommand::WriteTextRandomMode(mut text, targets, modes) => {
+            let supported: Vec<_> = modes
+                .into_iter()
+                .filter(|mode| display.supports_mode(*mode))
+                .collect();
+            match supported.choose(&mut rand::thread_rng()) {
+                Some(mode) => {
+                    tracing::debug!("Picked {mode:?} mode at random");
+                    text.mode = *mode;
+                }
+                None => {
+                    tracing::warn!("No modes left to pick from at random, using the sign's default")
+                }
+            }
+            write_text(display, displayed_tx, text, targets, None);
+        }
+        APICommand::WriteBanner(text, targets, picture) => {
+            write_text(display, displayed_tx, text, targets, Some(picture));
         }
         APICommand::ReadText(command, tx) => {
-            let read_text_command = Packet::new(vec![sign], vec![Command::ReadText(command)])
-                .encode()
-                .expect("making text command");
-
-            port.write(read_text_command.as_slice()).ok();
-
-            let mut bufreader = BufReader::new(port);
-
-            let mut buf: Vec<u8> = vec![];
-
-            bufreader.read_until(0x04, &mut buf).ok();
-
-            let (_, parse) = Packet::parse(buf.as_slice()).expect("error parsing response"); // TODO error handling
+            // The request may have timed out or its client hung up while the command was queued, in which case
+            // there's no one to read the text for. Once the read has been sent the sign's answer still has to be read
+            // to keep the line in step, so a client going away after that just has the answer dropped.
+            if tx.is_closed() {
+                tracing::debug!("Request went away, skipping reading file {}", command.label);
+                return;
+            }
+            match display.read_text(command) {
+                Ok(text) => {
+                    if tx.send(web_server::APIResponse::ReadText(text)).is_err() {
+                        tracing::debug!("Request went away, dropping the text read");
+                    }
+                }
+                Err(e) => tracing::error!("Failed to read text: {e}"),
+            }
+        }
+        APICommand::ConfigureMemory(files, keyboard_access, tx) => {
+            match display.configure_memory(&files, keyboard_access) {
+                Ok(()) => {
+                    tracing::info!("Reprovisioned sign memory with {} files", files.len());
+                    tx.send(web_server::APIResponse::Done).ok();
+                }
+                Err(e) => tracing::error!("Failed to reprovision sign memory: {e}"),
+            }
+        }
+        APICommand::WriteSpecial(special, tx) => {
+            tracing::info!("Running special function {special:?}");
+            match display.write_special(special) {
+                Ok(()) => {
+                    tx.send(web_server::APIResponse::Done).ok();
+                }
+                Err(e) => tracing::error!("Failed to run special function: {e}"),
+            }
+        }
+        APICommand::PlayScene(frames) => {
+            // Work out every frame up front so the time spent writing to the sign doesn't stretch the animation.
+            let mut at = tokio::time::Instant::now();
+            let plan: Vec<_> = frames
+                .into_iter()
+                .map(|frame| {
+                    let shown_at = at;
+                    at += frame.duration;
+                    (shown_at, WriteText::new('A', frame.text))
+                })
+                .collect();
 
-            if let Command::WriteText(WriteText { message: t, .. }) = &parse.commands[0] {
-                tx.send(web_server::APIResponse::ReadText(t.clone())).ok();
+            for (shown_at, text) in plan {
+                tokio::time::sleep_until(shown_at).await;
+                let displayed = DisplayedText::from(&text);
+                match display.write_text(text) {
+                    Ok(()) => {
+                        displayed_tx.send_replace(Some(displayed));
+                    }
+                    Err(e) => tracing::error!("Failed to write scene frame: {e}"),
+                }
             }
+            tokio::time::sleep_until(at).await;
         }
+        APICommand::NightMode(enabled, dimming) => {
+            if let Some(level) = dimming {
+                if let Err(e) = display.set_dimming(level) {
+                    tracing::error!("Failed to set dimming: {e}");
+                }
+            }
+            // The clock isn't published as the displayed text, so it is still there to put back in the morning.
+            let (text, banner) = if enabled {
+                (
+                    WriteText::new('A', call_time()).mode(TransitionMode::Clock),
+                    None,
+                )
+            } else {
+                match &*displayed_tx.borrow() {
+                    Some(displayed) => (
+                        WriteText::new(displayed.label, displayed.text.clone())
+                            .position(displayed.position)
+                            .mode(displayed.mode),
+                        displayed.banner,
+                    ),
+                    None => (WriteText::new('A', String::new()), None),
+                }
+            };
+            let res = match banner {
+                Some(picture) => display.write_banner(text, picture),
+                None => display.write_text(text),
+            };
+            match res {
+                Ok(()) => {
+                    tracing::info!("Night mode {}", if enabled { "started" } else { "ended" })
+                }
+                Err(e) => tracing::error!("Failed to change night mode: {e}"),
+            }
+        }
+        APICommand::ReadTime(tx) => match display.read_time() {
+            Ok(Some(time)) => {
+                tx.send(web_server::APIResponse::Time(time)).ok();
+            }
+            Ok(None) => tracing::debug!("No display has a clock to check"),
+            Err(e) => tracing::warn!("Failed to read the sign's clock: {e}"),
+        },
+    }
+}
+
+/// Writes text to the displays and publishes it as the text being shown.
+///
+/// # Arguments
+/// * `display`: The displays to write to.
+/// * `displayed_tx`: Channel the text currently shown on the sign is published to.
+/// * `text`: The text to write.
+/// * `targets`: Kinds of display to write to, or all of them if [`None`].
+/// * `banner`: Picture to draw the text into in big characters, or [`None`] to write it as it is.
+fn write_text(
+    display: &mut MirroredBackend,
+    displayed_tx: &watch::Sender<Option<DisplayedText>>,
+    text: WriteText,
+    targets: Option<Vec<Backend>>,
+    banner: Option<BannerPicture>,
+) {
+    let displayed = DisplayedText {
+        banner,
+        ..DisplayedText::from(&text)
+    };
+    let res = match (targets, banner) {
+        (Some(targets), Some(picture)) => display.write_banner_to(text, picture, &targets),
+        (Some(targets), None) => display.write_text_to(text, &targets),
+        (None, Some(picture)) => display.write_banner(text, picture),
+        (None, None) => display.write_text(text),
+    };
+    match res {
+        Ok(()) => {
+            displayed_tx.send_replace(Some(displayed));
+        }
+        Err(e) => tracing::error!("Failed to write text: {e}"),
     }
 }
 
 /// Serve the API.
 ///
+/// If systemd passed in a listening socket (socket activation) that is used, otherwise a new listener is bound on
+/// `port`. Once the listener is ready systemd is notified, so a `Type=notify` unit counts as started once the HTTP API
+/// is up, even if the displays are still being opened.
+///
 /// # Arguments
 /// * `app_state`: State shared between requests and the main application.
-/// * `port`: Port to serve on.
-async fn serve_api(app_state: AppState, port: u16) {
-    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
-    tracing::info!("Listening on {}", addr);
-    let _ = axum::Server::bind(&addr)
-        .serve(app(app_state).into_make_service())
-        .await;
+/// * `recorder`: Where to record every request and its response to, if anywhere.
+/// * `port`: Port to serve on when not socket activated.
+async fn serve_api(
+    app_state: AppState,
+    recorder: Option<ApiRecorder>,
+    port: u16,
+) -> Result<(), Error> {
+    let server = match systemd_listener() {
+        Some(listener) => {
+            tracing::info!("Listening on socket passed in by systemd");
+            axum::Server::from_tcp(listener).map_err(|e| Error::Http(e.into()))?
+        }
+        None => {
+            let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+            tracing::info!("Listening on {}", addr);
+            axum::Server::bind(&addr)
+        }
+    };
+    let app = match recorder {
+        Some(recorder) => web_server::record(app(app_state), recorder),
+        None => app(app_state),
+    };
+    let server = server.serve(app.into_make_service());
+
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        tracing::debug!("Failed to notify systemd of readiness: {e}");
+    }
+
+    server.await.map_err(|e| Error::Http(e.into()))
+}
+
+/// Takes the listening socket passed in by systemd socket activation, if there is one.
+///
+/// # Returns
+/// The first socket systemd passed in, or [`None`] if the service was not socket activated.
+fn systemd_listener() -> Option<TcpListener> {
+    let fd = sd_notify::listen_fds().ok()?.next()?;
+    // SAFETY: systemd hands ownership of the sockets starting at `SD_LISTEN_FDS_START` to this process, and nothing
+    // else takes ownership of them.
+    Some(unsafe { TcpListener::from_raw_fd(fd) })
 }
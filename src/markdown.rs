@@ -0,0 +1,168 @@
+/// Turns flashing text on.
+const FLASH_ON: &str = "\x071";
+/// Turns flashing text off.
+const FLASH_OFF: &str = "\x070";
+/// Switches to amber text.
+const AMBER: &str = "\x1C3";
+/// Switches back to the sign's automatic colour.
+const AUTO_COLOR: &str = "\x1CC";
+//TODO check these on the sign, the seven high font may not be the best fit for a heading on a two line sign
+/// Switches to the font used for headings.
+const HEADING_FONT: &str = "\x1A3";
+/// Switches back to the normal font.
+const NORMAL_FONT: &str = "\x1A1";
+/// Starts a new line on the sign.
+const NEW_LINE: &str = "\x0D";
+
+/// Text formatted for the sign from markdown.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Formatted {
+    /// The text with the sign's control codes in place of the markdown.
+    pub message: String,
+    /// Whether the text starts with a heading, in which case it belongs on the top line.
+    pub heading: bool,
+}
+
+/// Turns a small subset of markdown into the sign's formatting control codes, so emphasis can be added without
+/// knowing the Alpha protocol:
+///
+/// * `*text*` flashes.
+/// * `_text_` is amber. Only a `_` at the edge of a word counts, so `snake_case` is left as it is.
+/// * A line starting with `# ` is a heading, shown in a larger font.
+///
+/// A marker left open is closed at the end of its line, and `\` before a marker shows it as it is.
+///
+/// # Arguments
+/// * `text`: The markdown.
+///
+/// # Returns
+/// The formatted text.
+pub fn format(text: &str) -> Formatted {
+    let mut message = String::new();
+    let mut heading = false;
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            message.push_str(NEW_LINE);
+        }
+
+        let (line, is_heading) = match line.strip_prefix("# ") {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if is_heading {
+            heading |= i == 0;
+            message.push_str(HEADING_FONT);
+        }
+
+        let mut flashing = false;
+        let mut amber = false;
+        let mut previous = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let next = chars.peek().copied();
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped @ ('*' | '_' | '\\')) => message.push(escaped),
+                    Some(other) => {
+                        message.push('\\');
+                        message.push(other);
+                    }
+                    None => message.push('\\'),
+                },
+                '*' => {
+                    flashing = !flashing;
+                    message.push_str(if flashing { FLASH_ON } else { FLASH_OFF });
+                }
+                // Amber starts at the start of a word and ends at the end of one
+                '_' if (!amber && !is_word(previous)) || (amber && !is_word(next)) => {
+                    amber = !amber;
+                    message.push_str(if amber { AMBER } else { AUTO_COLOR });
+                }
+                c => message.push(c),
+            }
+            previous = Some(c);
+        }
+
+        if flashing {
+            message.push_str(FLASH_OFF);
+        }
+        if amber {
+            message.push_str(AUTO_COLOR);
+        }
+        if is_heading {
+            message.push_str(NORMAL_FONT);
+        }
+    }
+    Formatted { message, heading }
+}
+
+/// Whether a character is part of a word, as opposed to space, punctuation or the end of the line.
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(
+            format("Hello"),
+            Formatted {
+                message: "Hello".to_string(),
+                heading: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_emphasis() {
+        assert_eq!(
+            format("a *b* _c_").message,
+            format!("a {FLASH_ON}b{FLASH_OFF} {AMBER}c{AUTO_COLOR}")
+        );
+    }
+
+    #[test]
+    fn test_unclosed_emphasis() {
+        assert_eq!(
+            format("*a\nb").message,
+            format!("{FLASH_ON}a{FLASH_OFF}{NEW_LINE}b")
+        );
+    }
+
+    #[test]
+    fn test_underscore_in_word() {
+        assert_eq!(format("snake_case").message, "snake_case");
+        assert_eq!(
+            format("_snake_case_").message,
+            format!("{AMBER}snake_case{AUTO_COLOR}")
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(format("\\*a\\_b\\\\").message, "*a_b\\");
+        assert_eq!(format("\\n").message, "\\n");
+        assert_eq!(format("a\\").message, "a\\");
+    }
+
+    #[test]
+    fn test_headings() {
+        assert_eq!(
+            format("# Open\nCome in"),
+            Formatted {
+                message: format!("{HEADING_FONT}Open{NORMAL_FONT}{NEW_LINE}Come in"),
+                heading: true
+            }
+        );
+        let later = format("Come in\n# Open");
+        assert!(!later.heading);
+        assert_eq!(
+            later.message,
+            format!("Come in{NEW_LINE}{HEADING_FONT}Open{NORMAL_FONT}")
+        );
+        assert!(!format("#Open").heading);
+    }
+}
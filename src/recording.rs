@@ -0,0 +1,218 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alpha_sign::Packet;
+
+/// Which way bytes went over the serial port.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Bytes sent to the sign.
+    Tx,
+    /// Bytes received from the sign.
+    Rx,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Tx => write!(f, "tx"),
+            Direction::Rx => write!(f, "rx"),
+        }
+    }
+}
+
+/// Records bytes sent to and received from the sign so they can be replayed later.
+///
+/// Each line of a recording is `<milliseconds since the Unix epoch> <tx|rx> <bytes as hex>`.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    /// Creates a [`Recorder`] that appends to the given file.
+    ///
+    /// # Arguments
+    /// * `path`: Path of the file to record to. It is created if it doesn't exist.
+    ///
+    /// # Returns
+    /// A new [`Recorder`].
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Records some bytes. Failures are logged rather than returned so a full disk doesn't stop the sign working.
+    ///
+    /// # Arguments
+    /// * `direction`: Which way the bytes went.
+    /// * `bytes`: The bytes.
+    pub fn record(&mut self, direction: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let hex: String = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        if let Err(e) = writeln!(self.file, "{timestamp} {direction} {hex}") {
+            tracing::warn!("Failed to record serial traffic: {e}");
+        }
+    }
+}
+
+/// Reads a recording made by a [`Recorder`] and prints each packet in it, decoded where possible.
+///
+/// # Arguments
+/// * `path`: Path of the recording.
+pub fn replay(path: &Path) -> io::Result<()> {
+    for (timestamp, direction, packet) in read_packets(BufReader::new(File::open(path)?))? {
+        print_packet(&timestamp, direction, &packet);
+    }
+    Ok(())
+}
+
+/// Splits a recording back up into packets. A packet ends at an EOT byte or when the direction changes, so packets
+/// spread over several lines are put back together.
+///
+/// # Arguments
+/// * `reader`: The recording.
+///
+/// # Returns
+/// The timestamp of the line each packet started on, its direction and its bytes.
+fn read_packets(reader: impl BufRead) -> io::Result<Vec<(String, Direction, Vec<u8>)>> {
+    let mut packets = vec![];
+    let mut pending: Option<(String, Direction, Vec<u8>)> = None;
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let Some((timestamp, direction, bytes)) = parse_line(&line) else {
+            eprintln!("Skipping malformed line {}: {line}", number + 1);
+            continue;
+        };
+
+        for byte in bytes {
+            if pending
+                .as_ref()
+                .is_some_and(|(_, pending_direction, _)| *pending_direction != direction)
+            {
+                packets.extend(pending.take());
+            }
+            let (_, _, packet) =
+                pending.get_or_insert_with(|| (timestamp.to_string(), direction, vec![]));
+            packet.push(byte);
+            if byte == 0x04 {
+                packets.extend(pending.take());
+            }
+        }
+    }
+
+    packets.extend(pending);
+    Ok(packets)
+}
+
+/// Parses a line of a recording.
+///
+/// # Arguments
+/// * `line`: The line to parse.
+///
+/// # Returns
+/// The timestamp, direction and bytes on the line, or [`None`] if it is malformed.
+fn parse_line(line: &str) -> Option<(&str, Direction, Vec<u8>)> {
+    let mut fields = line.split_whitespace();
+    let timestamp = fields.next()?;
+    let direction = match fields.next()? {
+        "tx" => Direction::Tx,
+        "rx" => Direction::Rx,
+        _ => return None,
+    };
+    let hex = fields.next()?;
+    // from_str_radix would take a sign too, e.g. "+1"
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some((timestamp, direction, bytes))
+}
+
+/// Prints a packet from a recording, decoded if it parses and as hex if not.
+///
+/// # Arguments
+/// * `timestamp`: When the packet started.
+/// * `direction`: Which way the packet went.
+/// * `packet`: The bytes of the packet.
+fn print_packet(timestamp: &str, direction: Direction, packet: &[u8]) {
+    match Packet::parse(packet) {
+        Ok((_, decoded)) => println!("{timestamp} {direction} {decoded}"),
+        Err(_) => println!("{timestamp} {direction} undecodable: {packet:02X?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("1700000000000 tx 0102FF04"),
+            Some(("1700000000000", Direction::Tx, vec![0x01, 0x02, 0xFF, 0x04]))
+        );
+        assert_eq!(
+            parse_line("1700000000000 rx 0a"),
+            Some(("1700000000000", Direction::Rx, vec![0x0A]))
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_line() {
+        // Odd length
+        assert_eq!(parse_line("1700000000000 tx 010"), None);
+        // Not hex
+        assert_eq!(parse_line("1700000000000 tx 01ZZ"), None);
+        assert_eq!(parse_line("1700000000000 tx +1"), None);
+        // Unknown direction
+        assert_eq!(parse_line("1700000000000 up 01"), None);
+        // Missing fields
+        assert_eq!(parse_line("1700000000000 tx"), None);
+        assert_eq!(parse_line(""), None);
+    }
+
+    #[test]
+    fn test_replay_grouping() {
+        let path = std::env::temp_dir().join(format!("yhs-sign-test-{}.rec", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        // A packet split over two writes, then a reply without an EOT, then another packet
+        recorder.record(Direction::Tx, &[0x01, 0x5A]);
+        recorder.record(Direction::Tx, &[0x30, 0x04, 0x01]);
+        recorder.record(Direction::Rx, &[0x02, 0x03]);
+        recorder.record(Direction::Tx, &[0x04]);
+        drop(recorder);
+
+        let file = File::open(&path).unwrap();
+        let packets = read_packets(BufReader::new(file)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let packets: Vec<_> = packets
+            .into_iter()
+            .map(|(_, direction, packet)| (direction, packet))
+            .collect();
+        assert_eq!(
+            packets,
+            vec![
+                (Direction::Tx, vec![0x01, 0x5A, 0x30, 0x04]),
+                (Direction::Tx, vec![0x01]),
+                (Direction::Rx, vec![0x02, 0x03]),
+                (Direction::Tx, vec![0x04]),
+            ]
+        );
+    }
+}
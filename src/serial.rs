@@ -0,0 +1,558 @@
+// Without serial support the settings are still parsed from the command line, but nothing opens a port with them.
+#![cfg_attr(not(feature = "serial"), allow(dead_code))]
+
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    num::ParseIntError,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+#[cfg(feature = "serial")]
+use {
+    alpha_sign::{text::ReadText, Command, Packet, SignSelector},
+    serialport::{ClearBuffer, SerialPort},
+    std::io::{BufRead, BufReader},
+};
+
+use crate::transport::Connection;
+
+/// Number of data bits per character.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DataBits {
+    #[value(name = "7")]
+    Seven,
+    #[value(name = "8")]
+    Eight,
+}
+
+/// Parity bit mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Number of stop bits per character.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StopBits {
+    #[value(name = "1")]
+    One,
+    #[value(name = "2")]
+    Two,
+}
+
+#[cfg(feature = "serial")]
+impl From<DataBits> for serialport::DataBits {
+    fn from(data_bits: DataBits) -> Self {
+        match data_bits {
+            DataBits::Seven => serialport::DataBits::Seven,
+            DataBits::Eight => serialport::DataBits::Eight,
+        }
+    }
+}
+
+#[cfg(feature = "serial")]
+impl From<Parity> for serialport::Parity {
+    fn from(parity: Parity) -> Self {
+        match parity {
+            Parity::None => serialport::Parity::None,
+            Parity::Even => serialport::Parity::Even,
+            Parity::Odd => serialport::Parity::Odd,
+        }
+    }
+}
+
+#[cfg(feature = "serial")]
+impl From<StopBits> for serialport::StopBits {
+    fn from(stop_bits: StopBits) -> Self {
+        match stop_bits {
+            StopBits::One => serialport::StopBits::One,
+            StopBits::Two => serialport::StopBits::Two,
+        }
+    }
+}
+
+/// Baud rate to talk to the sign at, either fixed or found by probing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Baudrate {
+    Auto,
+    Fixed(u32),
+}
+
+impl Baudrate {
+    /// Baud rates Alpha signs support, in the order they are tried when probing.
+    pub const SUPPORTED: [u32; 5] = [1200, 2400, 4800, 9600, 19200];
+}
+
+impl FromStr for Baudrate {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            Ok(Baudrate::Auto)
+        } else {
+            s.parse().map(Baudrate::Fixed)
+        }
+    }
+}
+
+/// How characters are framed on the serial line, e.g. 8N1 or 7E1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Framing {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl Framing {
+    /// Framings Alpha signs are commonly configured for, in the order they are tried when probing.
+    pub const COMMON: [Framing; 4] = [
+        Framing::new(DataBits::Eight, Parity::None, StopBits::One),
+        Framing::new(DataBits::Seven, Parity::Even, StopBits::One),
+        Framing::new(DataBits::Seven, Parity::Even, StopBits::Two),
+        Framing::new(DataBits::Seven, Parity::Odd, StopBits::One),
+    ];
+
+    pub const fn new(data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> Self {
+        Self {
+            data_bits,
+            parity,
+            stop_bits,
+        }
+    }
+
+    /// Number of bits on the wire for each character, including the start bit.
+    pub const fn bits_per_char(&self) -> u32 {
+        let data_bits = match self.data_bits {
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity_bits = match self.parity {
+            Parity::None => 0,
+            Parity::Even | Parity::Odd => 1,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        1 + data_bits + parity_bits + stop_bits
+    }
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data_bits = match self.data_bits {
+            DataBits::Seven => '7',
+            DataBits::Eight => '8',
+        };
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => '1',
+            StopBits::Two => '2',
+        };
+        write!(f, "{data_bits}{parity}{stop_bits}")
+    }
+}
+
+/// Spaces packets out so each one has time to go over the wire before the next is sent.
+///
+/// Writes to a serial port return once the bytes are in the driver's buffer, not once they've been sent, so without
+/// this a burst of packets can overrun a slow sign.
+pub struct Pacer {
+    byte_time: Duration,
+    ready_at: Option<Instant>,
+}
+
+impl Pacer {
+    /// Creates a [`Pacer`] for a serial line.
+    ///
+    /// # Arguments
+    /// * `baudrate`: Baud rate of the line. Ptys are opened at 0, in which case packets aren't paced at all.
+    /// * `framing`: Data bits, parity and stop bits of the line.
+    ///
+    /// # Returns
+    /// A new [`Pacer`].
+    pub fn new(baudrate: u32, framing: Framing) -> Self {
+        let byte_time = match baudrate {
+            0 => Duration::ZERO,
+            baudrate => Duration::from_secs(u64::from(framing.bits_per_char())) / baudrate,
+        };
+        Self {
+            byte_time,
+            ready_at: None,
+        }
+    }
+
+    /// Blocks until the last packet has had time to be sent.
+    pub fn wait(&mut self) {
+        if let Some(ready_at) = self.ready_at.take() {
+            thread::sleep(ready_at.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    /// Notes that a packet has just been written.
+    ///
+    /// # Arguments
+    /// * `bytes`: Length of the packet.
+    pub fn sent(&mut self, bytes: usize) {
+        let bytes = u32::try_from(bytes).unwrap_or(u32::MAX);
+        self.ready_at = Some(Instant::now() + self.byte_time.saturating_mul(bytes));
+    }
+}
+
+/// Opens a serial port to the sign.
+///
+/// # Arguments
+/// * `path`: Path of the serial device.
+/// * `baudrate`: Baud rate to use.
+/// * `framing`: Data bits, parity and stop bits to use.
+///
+/// # Returns
+/// The opened port.
+#[cfg(feature = "serial")]
+pub fn open(
+    path: &str,
+    baudrate: u32,
+    framing: Framing,
+) -> serialport::Result<Box<dyn SerialPort>> {
+    serialport::new(path, baudrate)
+        .timeout(Duration::from_millis(1000))
+        .data_bits(framing.data_bits.into())
+        .parity(framing.parity.into())
+        .stop_bits(framing.stop_bits.into())
+        .open()
+}
+
+/// Sends a harmless read command to the sign and checks that a parseable response comes back.
+///
+/// # Arguments
+/// * `port`: The serial port to probe.
+/// * `sign`: The sign to address.
+///
+/// # Returns
+/// Whether the sign responded with a valid packet.
+#[cfg(feature = "serial")]
+pub fn sign_responds(port: &mut Box<dyn SerialPort>, sign: SignSelector) -> bool {
+    let Ok(request) = Packet::new(vec![sign], vec![Command::ReadText(ReadText::new('A'))]).encode()
+    else {
+        return false;
+    };
+
+    port.clear(ClearBuffer::Input).ok();
+    if port.write_all(request.as_slice()).is_err() {
+        return false;
+    }
+
+    let mut buf: Vec<u8> = vec![];
+    if BufReader::new(port).read_until(0x04, &mut buf).is_err() {
+        return false;
+    }
+
+    Packet::parse(buf.as_slice()).is_ok()
+}
+
+/// Tries each combination of baud rate and framing until the sign responds.
+///
+/// # Arguments
+/// * `path`: Path of the serial device.
+/// * `baudrates`: Baud rates to try, in order.
+/// * `framings`: Framings to try at each baud rate, in order.
+/// * `sign`: The sign to address.
+///
+/// # Returns
+/// The opened port along with the baud rate and framing the sign responded to, or [`None`] if it never did.
+#[cfg(feature = "serial")]
+pub fn probe(
+    path: &str,
+    baudrates: &[u32],
+    framings: &[Framing],
+    sign: SignSelector,
+) -> Option<(Box<dyn SerialPort>, u32, Framing)> {
+    for &baudrate in baudrates {
+        for &framing in framings {
+            tracing::debug!("Probing sign at {baudrate} baud with {framing} framing");
+            match open(path, baudrate, framing) {
+                Ok(mut port) => {
+                    if sign_responds(&mut port, sign) {
+                        return Some((port, baudrate, framing));
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "Failed to open {path} at {baudrate} baud with {framing} framing: {e}"
+                ),
+            }
+        }
+    }
+    None
+}
+
+/// Which device a `FailoverPort` is using and how many times it has switched, shared so it can be reported while the
+/// port is in use.
+#[derive(Debug, Default)]
+pub struct FailoverStatus {
+    on_backup: AtomicBool,
+    failovers: AtomicU32,
+}
+
+impl FailoverStatus {
+    /// Whether the backup device is the one in use.
+    pub fn on_backup(&self) -> bool {
+        self.on_backup.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the port has switched device since the service started.
+    pub fn failovers(&self) -> u32 {
+        self.failovers.load(Ordering::Relaxed)
+    }
+}
+
+/// Opens a device by path, used by a [`FailoverPort`] to open the device it switches to.
+pub type Reopen = Box<dyn FnMut(&str) -> io::Result<Box<dyn Connection>> + Send>;
+
+/// A serial port that switches over to a backup device when the active one fails several times in a row, e.g. when a
+/// USB serial adapter dies. Timeouts waiting for the sign count as failures.
+pub struct FailoverPort {
+    paths: [String; 2],
+    active: usize,
+    port: Option<Box<dyn Connection>>,
+    reopen: Reopen,
+    failures: u32,
+    status: Arc<FailoverStatus>,
+}
+
+impl FailoverPort {
+    /// Number of failures in a row after which the other port is switched to.
+    const FAILURES_BEFORE_SWITCHING: u32 = 3;
+
+    /// Creates a new [`FailoverPort`].
+    ///
+    /// # Arguments
+    /// * `paths`: Paths of the primary and backup serial devices.
+    /// * `active`: Index into `paths` of the device `port` is open on.
+    /// * `port`: The open port.
+    /// * `reopen`: Opens the other device when switching, with the same settings as `port`.
+    /// * `status`: Where to report which device is in use and how many times it has switched.
+    ///
+    /// # Returns
+    /// A new [`FailoverPort`].
+    pub fn new(
+        paths: [String; 2],
+        active: usize,
+        port: Box<dyn Connection>,
+        reopen: Reopen,
+        status: Arc<FailoverStatus>,
+    ) -> Self {
+        status.on_backup.store(active == 1, Ordering::Relaxed);
+        Self {
+            paths,
+            active,
+            port: Some(port),
+            reopen,
+            failures: 0,
+            status,
+        }
+    }
+
+    /// Counts failures in a row, switching ports once there have been too many.
+    ///
+    /// # Arguments
+    /// * `res`: Result of an operation on the active port.
+    ///
+    /// # Returns
+    /// `res`, unchanged.
+    fn track<T>(&mut self, res: io::Result<T>) -> io::Result<T> {
+        match &res {
+            Ok(_) => self.failures = 0,
+            Err(_) => {
+                self.failures += 1;
+                if self.failures >= Self::FAILURES_BEFORE_SWITCHING {
+                    self.switch();
+                }
+            }
+        }
+        res
+    }
+
+    /// Switches to the other port.
+    fn switch(&mut self) {
+        self.failures = 0;
+        self.active = 1 - self.active;
+        self.status
+            .on_backup
+            .store(self.active == 1, Ordering::Relaxed);
+        self.status.failovers.fetch_add(1, Ordering::Relaxed);
+        let path = &self.paths[self.active];
+        tracing::warn!("Serial port keeps failing, switching to {path}");
+        self.port = match (self.reopen)(path) {
+            Ok(port) => Some(port),
+            Err(e) => {
+                tracing::error!("Failed to open {path}: {e}");
+                None
+            }
+        };
+    }
+
+    fn port(&mut self) -> io::Result<&mut Box<dyn Connection>> {
+        self.port
+            .as_mut()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotConnected, "no serial port is open"))
+    }
+}
+
+impl Read for FailoverPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.port().and_then(|port| port.read(buf));
+        self.track(res)
+    }
+}
+
+impl Write for FailoverPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let res = self.port().and_then(|port| port.write(buf));
+        self.track(res)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let res = self.port().and_then(|port| port.flush());
+        self.track(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::FakeConnection;
+
+    use super::*;
+
+    #[test]
+    fn test_bits_per_char() {
+        let bits: Vec<u32> = Framing::COMMON.iter().map(Framing::bits_per_char).collect();
+        // 8N1, 7E1, 7E2 and 7O1
+        assert_eq!(bits, vec![10, 10, 11, 10]);
+    }
+
+    #[test]
+    fn test_byte_time() {
+        let framing = Framing::new(DataBits::Seven, Parity::Even, StopBits::Two);
+        assert_eq!(
+            Pacer::new(1200, framing).byte_time,
+            Duration::from_secs(11) / 1200
+        );
+        assert_eq!(
+            Pacer::new(9600, Framing::COMMON[0]).byte_time,
+            Duration::from_nanos(1_041_666)
+        );
+    }
+
+    #[test]
+    fn test_pacer_waits_for_packet() {
+        // 96 bytes of 10 bits at 9600 baud take 100ms to send
+        let mut pacer = Pacer::new(9600, Framing::COMMON[0]);
+        pacer.sent(96);
+        let start = Instant::now();
+        pacer.wait();
+        assert!(start.elapsed() >= Duration::from_millis(99));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Nothing more has been sent, so there's nothing to wait for
+        let start = Instant::now();
+        pacer.wait();
+        assert!(start.elapsed() < Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_no_pacing_at_zero_baud() {
+        let mut pacer = Pacer::new(0, Framing::COMMON[0]);
+        assert_eq!(pacer.byte_time, Duration::ZERO);
+        pacer.sent(usize::MAX);
+        let start = Instant::now();
+        pacer.wait();
+        assert!(start.elapsed() < Duration::from_millis(10));
+    }
+
+    /// Makes a [`FailoverPort`] open on `primary`, which opens `primary` or `backup` when it switches, or fails to if
+    /// `backup` is [`None`].
+    fn failover_port(
+        primary: &FakeConnection,
+        backup: Option<&FakeConnection>,
+    ) -> (FailoverPort, Arc<FailoverStatus>) {
+        let status = Arc::new(FailoverStatus::default());
+        let (reopened_primary, backup) = (primary.clone(), backup.cloned());
+        let reopen: Reopen = Box::new(move |path| match (path, &backup) {
+            ("primary", _) => Ok(Box::new(reopened_primary.clone()) as Box<dyn Connection>),
+            ("backup", Some(backup)) => Ok(Box::new(backup.clone()) as Box<dyn Connection>),
+            _ => Err(ErrorKind::NotFound.into()),
+        });
+        let port = FailoverPort::new(
+            ["primary".to_string(), "backup".to_string()],
+            0,
+            Box::new(primary.clone()),
+            reopen,
+            status.clone(),
+        );
+        (port, status)
+    }
+
+    #[test]
+    fn test_failover_after_failures() {
+        let primary = FakeConnection::default();
+        let backup = FakeConnection::default();
+        let (mut port, status) = failover_port(&primary, Some(&backup));
+
+        // A success in between starts the count again
+        primary.broken.store(true, Ordering::Relaxed);
+        assert!(port.write(b"a").is_err());
+        assert!(port.write(b"a").is_err());
+        primary.broken.store(false, Ordering::Relaxed);
+        port.write_all(b"ok").unwrap();
+        primary.broken.store(true, Ordering::Relaxed);
+        assert!(port.write(b"a").is_err());
+        assert!(port.write(b"a").is_err());
+        assert!(!status.on_backup());
+
+        // The third failure in a row switches
+        assert!(port.write(b"a").is_err());
+        assert!(status.on_backup());
+        assert_eq!(status.failovers(), 1);
+        port.write_all(b"backup").unwrap();
+        assert_eq!(*primary.written.lock().unwrap(), b"ok");
+        assert_eq!(*backup.written.lock().unwrap(), b"backup");
+    }
+
+    #[test]
+    fn test_failover_back_when_backup_wont_open() {
+        let primary = FakeConnection::default();
+        let (mut port, status) = failover_port(&primary, None);
+
+        primary.broken.store(true, Ordering::Relaxed);
+        for _ in 0..3 {
+            assert!(port.write(b"a").is_err());
+        }
+        assert!(status.on_backup());
+        assert_eq!(status.failovers(), 1);
+
+        // With nothing open every write fails, so it switches straight back
+        primary.broken.store(false, Ordering::Relaxed);
+        for _ in 0..3 {
+            let e = port.write(b"a").unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::NotConnected);
+        }
+        assert!(!status.on_backup());
+        assert_eq!(status.failovers(), 2);
+        port.write_all(b"primary").unwrap();
+        assert_eq!(*primary.written.lock().unwrap(), b"primary");
+    }
+}
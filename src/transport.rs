@@ -0,0 +1,76 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+/// A byte stream to a display, e.g. a serial port or a TCP connection to a serial-to-network adapter.
+pub trait Connection: Read + Write + Send {}
+
+impl<T: Read + Write + Send + ?Sized> Connection for T {}
+
+/// Connects to a display over TCP.
+///
+/// # Arguments
+/// * `address`: Address to connect to, as `host:port`.
+///
+/// # Returns
+/// The connection.
+pub fn connect_tcp(address: &str) -> io::Result<Box<dyn Connection>> {
+    let stream = TcpStream::connect(address)?;
+    // Match the serial port timeout so a sign that never answers a read doesn't hang the sign loop.
+    stream.set_read_timeout(Some(Duration::from_millis(1000)))?;
+    stream.set_nodelay(true)?;
+    Ok(Box::new(stream))
+}
+
+/// A connection for tests, which keeps what is written to it, reads back bytes queued up beforehand and can be made to
+/// fail. Clones share the same buffers, so one can be kept to check on a connection handed off to a display.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct FakeConnection {
+    /// Bytes written to the connection.
+    pub written: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    /// Bytes still to be read from the connection.
+    pub to_read: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<u8>>>,
+    /// Whether reads and writes fail, as if the device had been unplugged.
+    pub broken: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(test)]
+impl FakeConnection {
+    fn check(&self) -> io::Result<()> {
+        if self.broken.load(std::sync::atomic::Ordering::Relaxed) {
+            Err(io::ErrorKind::BrokenPipe.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+impl Read for FakeConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check()?;
+        let mut to_read = self.to_read.lock().unwrap();
+        if to_read.is_empty() {
+            // Like a serial port with nothing to read
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        // A byte at a time, so nothing after the end of a packet is read into a buffer and lost with it
+        let len = buf.len().min(1);
+        to_read.read(&mut buf[..len])
+    }
+}
+
+#[cfg(test)]
+impl Write for FakeConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+        self.written.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check()
+    }
+}
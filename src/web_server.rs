@@ -1,44 +1,207 @@
 use std::{sync::Arc, time::Duration};
 
+use crate::{
+    api_recording::{record_exchanges, ApiRecorder},
+    config::{Config, Frame, MemoryFile},
+    display::{Backend, BannerPicture},
+    duration::HumanDuration,
+    markdown,
+    serial::FailoverStatus,
+};
 use alpha_sign::{
-    text::{ReadText, WriteText},
-    Packet,
+    text::{ReadText, TextPosition, TransitionMode, WriteText},
+    write_special::{
+        ClearMemoryAndFlash, RunSequenceType, SetRunSequence, SetTimeFormat, SoftReset,
+        ToggleSpeaker, WriteSpecial,
+    },
 };
 use axum::{
     body::Bytes,
-    extract::{Path, State},
-    http::{header, HeaderValue, StatusCode},
-    response::IntoResponse,
-    routing::{get, put},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        DefaultBodyLimit, Extension, Path, Query, State,
+    },
+    http::{header, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
-use tokio::sync::oneshot::{self, Sender};
+use serde::{Deserialize, Serialize, Serializer};
+use time::Time;
+use tokio::sync::{
+    mpsc::error::SendError,
+    oneshot::{self, Sender},
+    watch, Mutex,
+};
 use tower::ServiceBuilder;
 use tower_http::{
+    request_id::{MakeRequestId, MakeRequestUuid, RequestId},
     services::ServeDir,
+    set_header::SetResponseHeaderLayer,
     timeout::TimeoutLayer,
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
     LatencyUnit, ServiceBuilderExt,
 };
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Largest request body accepted, far more text than the sign can hold. Bigger bodies are rejected with 413 Payload
+/// Too Large before they are read into memory.
+const MAX_BODY_BYTES: usize = 16 * 1024;
+
+/// Longest an admin request is given, longer than other requests as reprovisioning backs up and restores every file
+/// on the sign, which can take minutes at low baud rates.
+const ADMIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Longest a GET to `/current` will wait for the displayed text to change.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Records every request to an app and the response to it, for the API regression tests.
+///
+/// # Arguments
+/// * `app`: The app, from [`app`].
+/// * `recorder`: Where to record to.
+///
+/// # Returns
+/// The app, recording as it goes.
+pub fn record(app: Router, recorder: ApiRecorder) -> Router {
+    app.layer(middleware::from_fn_with_state(
+        (recorder, MAX_BODY_BYTES),
+        record_exchanges,
+    ))
+}
+
+/// Handle for changing the filter that decides which logs are shown.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
 
 /// State shared between the main application and the HTTP application.
 #[derive(Clone)]
 pub struct AppState {
     /// Message channel into which commands can be sent.
-    command_tx: tokio::sync::mpsc::UnboundedSender<APICommand>,
+    command_tx: tokio::sync::mpsc::UnboundedSender<QueuedCommand>,
+    /// The current config, updated when it is reloaded.
+    config: watch::Receiver<Config>,
+    /// The text currently shown on the sign, if any has been written yet.
+    displayed: watch::Receiver<Option<DisplayedText>>,
+    /// Whether the displays have been opened yet.
+    connected: watch::Receiver<bool>,
+    /// Which serial device the sign is on, if there is a backup one to switch to.
+    failover: Option<Arc<FailoverStatus>>,
+    /// How many minutes the sign's clock was ahead of the host's when it was last checked, if it has been.
+    clock_drift: watch::Receiver<Option<i64>>,
+    /// Handle for changing which logs are shown.
+    log_filter: LogFilterHandle,
+    /// Whether the sign's IR keyboard is locked out, held so reprovisions and run sequences keep to it.
+    keyboard: Arc<Mutex<KeyboardLock>>,
+}
+
+/// Whether the sign's IR keyboard can change it, starting from `keyboard_access` in the config.
+#[derive(Debug)]
+struct KeyboardLock {
+    /// Whether the keyboard can edit the sign's files and run sequence.
+    access: bool,
+    /// The last run sequence sent, so it can be sent again when the lock changes.
+    run_sequence: Option<SetRunSequence>,
+}
+
+/// Text that has been written to the sign.
+#[derive(Clone, Debug, Serialize)]
+pub struct DisplayedText {
+    /// Label of the file the text was written to.
+    pub label: char,
+    /// The text itself.
+    pub text: String,
+    /// Where on the sign the text is shown, by name, e.g. `top_line`.
+    #[serde(serialize_with = "serialize_position")]
+    pub position: TextPosition,
+    /// How the text appears on the sign, by name, e.g. `roll_up`.
+    #[serde(serialize_with = "serialize_mode")]
+    pub mode: TransitionMode,
+    /// The picture the text is drawn into in big characters, if it's a banner.
+    #[serde(skip)]
+    pub banner: Option<BannerPicture>,
+}
+
+impl From<&WriteText> for DisplayedText {
+    fn from(text: &WriteText) -> Self {
+        Self {
+            label: text.label,
+            text: text.message.clone(),
+            position: text.position,
+            mode: text.mode,
+            banner: None,
+        }
+    }
+}
+
+/// Serializes a position by the name a PUT to `/text/:textKey` takes.
+fn serialize_position<S: Serializer>(
+    position: &TextPosition,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_name(&TextPosition::NAMES, position, serializer)
+}
+
+/// Serializes a transition mode by the name a PUT to `/text/:textKey` takes.
+fn serialize_mode<S: Serializer>(mode: &TransitionMode, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_name(&TransitionMode::NAMES, mode, serializer)
+}
+
+/// Serializes a value by its name in a table of names.
+///
+/// # Arguments
+/// * `names`: Each value's name.
+/// * `value`: The value to serialize.
+/// * `serializer`: The serializer.
+fn serialize_name<T: PartialEq, S: Serializer>(
+    names: &[(&str, T)],
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let (name, _) = names
+        .iter()
+        .find(|(_, named)| named == value)
+        .ok_or_else(|| serde::ser::Error::custom("value has no name"))?;
+    serializer.serialize_str(name)
 }
 
 /// all possible responses to an API command.
 pub enum APIResponse {
     ReadText(String),
+    /// The time of day on the sign's clock.
+    Time(Time),
+    /// The command was carried out.
+    Done,
 }
 
 /// Enumerates all messages that can be sent from the webserver to the main program.
 /// I don't just use sign commands here because the web server will likely be sending more abstract commands (like "set rotation texts") that are not included in the base sign protocol and handled instead in software.
 pub enum APICommand {
-    WriteText(WriteText),
+    /// Write text to the displays of the given kinds, or all of them if [`None`].
+    WriteText(WriteText, Option<Vec<Backend>>),
+    /// Write text as with [`APICommand::WriteText`], using one of the given modes picked at random from those the
+    /// displays support.
+    WriteTextRandomMode(WriteText, Option<Vec<Backend>>, Vec<TransitionMode>),
+    /// Write text as with [`APICommand::WriteText`], in big characters drawn into the given picture.
+    WriteBanner(WriteText, Option<Vec<Backend>>, BannerPicture),
     ReadText(ReadText, Sender<APIResponse>),
+    /// Lay out the sign's memory as the given files, keeping their text, and set whether the IR keyboard can edit them.
+    ConfigureMemory(Vec<MemoryFile>, bool, Sender<APIResponse>),
+    /// Run one of the sign's special functions.
+    WriteSpecial(WriteSpecial, Sender<APIResponse>),
+    /// Show each frame of a scene in turn. Other commands wait until it has finished.
+    PlayScene(Vec<Frame>),
+    /// Switch night mode on or off, setting the dimming register to the given value if there is one.
+    NightMode(bool, Option<u8>),
+    /// Read the sign's clock, to check it for drift.
+    ReadTime(Sender<APIResponse>),
+}
+
+/// A command waiting to be handled by the sign loop.
+pub struct QueuedCommand {
+    /// ID of the HTTP request that sent the command, so what happens to it can be matched up with the request in logs.
+    pub request_id: Option<String>,
+    pub command: APICommand,
 }
 
 impl AppState {
@@ -46,11 +209,77 @@ impl AppState {
     ///
     /// # Arguments
     /// * `command_tx`: Channel into which commands can be sent.
+    /// * `config`: Receiver for the current config.
+    /// * `displayed`: Receiver for the text currently shown on the sign.
+    /// * `connected`: Receiver for whether the displays have been opened yet.
+    /// * `failover`: Which serial device the sign is on, if there is a backup one to switch to.
+    /// * `clock_drift`: Receiver for how far the sign's clock has drifted, in minutes.
+    /// * `log_filter`: Handle for changing which logs are shown.
     ///
     /// # Returns
     /// A new [`AppState`].
-    pub fn new(command_tx: tokio::sync::mpsc::UnboundedSender<APICommand>) -> Self {
-        Self { command_tx }
+    pub fn new(
+        command_tx: tokio::sync::mpsc::UnboundedSender<QueuedCommand>,
+        config: watch::Receiver<Config>,
+        displayed: watch::Receiver<Option<DisplayedText>>,
+        connected: watch::Receiver<bool>,
+        failover: Option<Arc<FailoverStatus>>,
+        clock_drift: watch::Receiver<Option<i64>>,
+        log_filter: LogFilterHandle,
+    ) -> Self {
+        let keyboard = Arc::new(Mutex::new(KeyboardLock {
+            access: config.borrow().keyboard_access,
+            run_sequence: None,
+        }));
+        Self {
+            command_tx,
+            config,
+            displayed,
+            connected,
+            failover,
+            clock_drift,
+            log_filter,
+            keyboard,
+        }
+    }
+
+    /// Queues a command for the sign loop.
+    ///
+    /// # Arguments
+    /// * `request_id`: ID of the request sending the command.
+    /// * `command`: The command.
+    fn send(
+        &self,
+        request_id: &RequestId,
+        command: APICommand,
+    ) -> Result<(), SendError<QueuedCommand>> {
+        self.command_tx.send(QueuedCommand {
+            request_id: request_id.header_value().to_str().ok().map(str::to_string),
+            command,
+        })
+    }
+}
+
+/// Uses the trace ID of an incoming W3C `traceparent` header as the request ID, so requests can be followed across
+/// services, and otherwise makes up a random one.
+#[derive(Clone, Copy)]
+struct MakeRequestIdFromTraceparent;
+
+impl MakeRequestId for MakeRequestIdFromTraceparent {
+    fn make_request_id<B>(&mut self, request: &Request<B>) -> Option<RequestId> {
+        let trace_id = request
+            .headers()
+            .get("traceparent")
+            .and_then(|traceparent| traceparent.to_str().ok())
+            .and_then(|traceparent| traceparent.split('-').nth(1))
+            .filter(|trace_id| {
+                trace_id.len() == 32 && trace_id.bytes().all(|b| b.is_ascii_hexdigit())
+            })
+            .and_then(|trace_id| HeaderValue::from_str(trace_id).ok());
+        match trace_id {
+            Some(trace_id) => Some(RequestId::new(trace_id)),
+            None => MakeRequestUuid.make_request_id(request),
+        }
     }
 }
 
@@ -64,6 +293,9 @@ impl AppState {
 pub fn app(state: AppState) -> Router {
     let sensitive_headers: Arc<[_]> = vec![header::AUTHORIZATION, header::COOKIE].into();
     let middleware = ServiceBuilder::new()
+        // Tag each request with an ID, keeping any `x-request-id` the caller sent, and send it back in the response
+        .set_x_request_id(MakeRequestIdFromTraceparent)
+        .propagate_x_request_id()
         // Mark the `Authorization` and `Cookie` headers as sensitive so it doesn't show in logs
         .sensitive_request_headers(sensitive_headers.clone())
         // Add high level tracing/logging to all requests
@@ -76,8 +308,6 @@ pub fn app(state: AppState) -> Router {
                 .on_response(DefaultOnResponse::new().include_headers(true).latency_unit(LatencyUnit::Micros)),
         )
         .sensitive_response_headers(sensitive_headers)
-        // Set a timeout
-        .layer(TimeoutLayer::new(Duration::from_secs(10)))
         // Box the response body so it implements `Default` which is required by axum
         .map_response_body(axum::body::boxed)
         // Compress responses
@@ -88,15 +318,109 @@ pub fn app(state: AppState) -> Router {
             HeaderValue::from_static("application/octet-stream"),
         );
 
+    // The unversioned routes are kept for existing scripts, marked deprecated so callers know to move over.
+    let deprecation = SetResponseHeaderLayer::overriding(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static("true"),
+    );
+    let deprecated_api = api().layer(deprecation.clone());
+
     Router::new()
-        //.route("/script", post(post_script_handler))
-        .route("/text/:textKey", put(put_text_handler))
-        .route("/text/get/:label", get(get_text_handler))
+        .nest("/api/v1", api())
+        .merge(deprecated_api)
+        .route("/live", get(live_handler))
+        .route("/live/ws", get(live_ws_handler))
+        // Set a timeout, on everything but long polls and admin requests which set their own
+        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        .nest("/api/v1/admin", admin_api(state.clone()))
+        .route("/api/v1/current", get(current_handler))
+        .route("/current", get(current_handler).layer(deprecation))
         .layer(middleware)
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
         .with_state(state)
         .fallback_service(ServeDir::new("static"))
 }
 
+/// Creates the routes of the JSON API, without a version prefix.
+///
+/// # Returns
+/// A [`Router`] for the API.
+fn api() -> Router<AppState> {
+    Router::new()
+        //.route("/script", post(post_script_handler))
+        .route("/text/:textKey", put(put_text_handler))
+        .route("/text/get/:label", get(get_text_handler))
+        .route("/scene/:name", post(play_scene_handler))
+        .route("/status", get(status_handler))
+}
+
+/// Creates the `/admin` routes, relative to `/admin`. These need the admin token from the config, as do requests for
+/// admin routes that don't exist, so the routes can't be found without it.
+///
+/// # Arguments
+/// * `state`: Shared application state, for checking the token.
+///
+/// # Returns
+/// A [`Router`] for the admin routes.
+fn admin_api(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/reprovision", post(reprovision_handler))
+        .route("/keyboard", put(keyboard_handler))
+        .route("/night-mode", put(night_mode_handler))
+        .route("/soft-reset", post(soft_reset_handler))
+        .route("/clear-memory", post(clear_memory_handler))
+        .route("/run-sequence", put(run_sequence_handler))
+        .route("/time-format", put(time_format_handler))
+        .route("/speaker", put(speaker_handler))
+        .route(
+            "/log-level",
+            get(get_log_level_handler).put(put_log_level_handler),
+        )
+        .fallback(|| async { StatusCode::NOT_FOUND })
+        .layer(TimeoutLayer::new(ADMIN_TIMEOUT))
+        .layer(middleware::from_fn_with_state(state, require_admin_token))
+}
+
+/// Lets a request through only if it carries the admin token from the config as a bearer token.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request`: The request.
+/// * `next`: The rest of the stack.
+///
+/// # Returns
+/// The response from the rest of the stack, 401 if the token is missing or wrong, or 403 if no token is configured.
+async fn require_admin_token<B>(
+    state: State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(admin_token) = state.config.borrow().admin_token.clone() else {
+        tracing::warn!("Refusing admin request, no admin_token is configured");
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response(),
+    }
+}
+
+/// Compares two byte strings in time that depends only on their lengths, so a token can't be guessed a byte at a time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
 /// Parameters for a PUT to `/text/:textKey`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PutTextParams {
@@ -110,12 +434,58 @@ pub struct PutTextParams {
 pub struct PutTextRequest {
     /// Text to display.
     pub text: String,
+    /// Label of the file to write to, `A` if not given.
+    pub label: Option<char>,
+    /// Where on the sign to show the text, e.g. `top_line`.
+    pub position: Option<String>,
+    /// How the text appears on the sign, e.g. `roll_up`.
+    pub mode: Option<String>,
+    /// Whether `text` uses the markdown subset understood by [`markdown::format`].
+    #[serde(default)]
+    pub markdown: bool,
+}
+
+impl PutTextRequest {
+    /// Name of the mode that picks a different transition mode at random each time.
+    const RANDOM_MODE: &'static str = "random";
+
+    /// Turns the request into the text to write to the sign.
+    ///
+    /// # Returns
+    /// The text to write and whether its mode should be picked at random, or [`None`] if the label, position or mode
+    /// are invalid.
+    fn into_write_text(self) -> Option<(WriteText, bool)> {
+        let label = self.label.unwrap_or('A');
+        if !label.is_ascii_graphic() {
+            return None;
+        }
+        let mut text = if self.markdown {
+            let formatted = markdown::format(&self.text);
+            let text = WriteText::new(label, formatted.message);
+            if formatted.heading {
+                text.position(TextPosition::TopLine)
+            } else {
+                text
+            }
+        } else {
+            WriteText::new(label, self.text)
+        };
+        if let Some(position) = self.position {
+            text = text.position(position.parse().ok()?);
+        }
+        let random_mode = self.mode.as_deref() == Some(Self::RANDOM_MODE);
+        if let Some(mode) = self.mode.filter(|_| !random_mode) {
+            text = text.mode(mode.parse().ok()?);
+        }
+        Some((text, random_mode))
+    }
 }
 
 /// Handles a PUT to `/text/:textKey`.
 ///
 /// # Arguments
 /// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
 /// * `text_key`: Key to write to.
 /// * `body`: Request body.
 ///
@@ -124,20 +494,45 @@ pub struct PutTextRequest {
 #[axum::debug_handler]
 async fn put_text_handler(
     state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
     Path(PutTextParams { text_key }): Path<PutTextParams>,
     Json(body): Json<PutTextRequest>,
 ) -> impl IntoResponse {
-    // TODO: We should have a list of keys that isn't hard-coded.
-    if ["test", "lulzbot", "anycubic"].contains(&text_key.as_str()) {
-        state
-            .command_tx
-            .send(APICommand::WriteText(WriteText::new('A', body.text)))
-            .ok(); // TODO: Handle errors
-
-        StatusCode::OK
+    let (targets, exclusions, banner) = {
+        let config = state.config.borrow();
+        if !config.text_keys.contains(&text_key) {
+            return StatusCode::FORBIDDEN;
+        }
+        (
+            config.route(&text_key).map(<[Backend]>::to_vec),
+            config.random_mode_exclusions.clone(),
+            config.banner_for(&text_key),
+        )
+    };
+
+    let Some((text, random_mode)) = body.into_write_text() else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let command = if let Some(picture) = banner {
+        // Random modes aren't picked for banners, which keep the mode they were given
+        APICommand::WriteBanner(text, targets, picture)
+    } else if random_mode {
+        // The clock mode replaces the text with the time, so it's never a sensible surprise.
+        let modes = TransitionMode::NAMES
+            .iter()
+            .map(|(_, mode)| *mode)
+            .filter(|mode| *mode != TransitionMode::Clock && !exclusions.contains(mode))
+            .collect();
+        APICommand::WriteTextRandomMode(text, targets, modes)
     } else {
-        StatusCode::FORBIDDEN
+        APICommand::WriteText(text, targets)
+    };
+    if state.send(&request_id, command).is_err() {
+        tracing::error!("Sign loop has stopped, dropping text");
+        return StatusCode::SERVICE_UNAVAILABLE;
     }
+
+    StatusCode::OK
 }
 
 #[derive(Serialize)]
@@ -155,16 +550,932 @@ pub struct GetTextParams {
 #[axum::debug_handler]
 async fn get_text_handler(
     state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
     Path(GetTextParams { label }): Path<GetTextParams>,
 ) -> impl IntoResponse {
     let (tx, rx) = oneshot::channel::<APIResponse>();
-    state
-        .command_tx
-        .send(APICommand::ReadText(ReadText::new(label), tx))
-        .ok(); // TODO handle errors
+    if state
+        .send(&request_id, APICommand::ReadText(ReadText::new(label), tx))
+        .is_err()
+    {
+        tracing::error!("Sign loop has stopped, can't read text");
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
 
     match rx.await {
         Ok(APIResponse::ReadText(t)) => Json(GetTextResponse { text: t }).into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) | Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Body of a response to a GET to `/status`.
+#[derive(Debug, Serialize)]
+pub struct StatusResponse {
+    /// `connected` once the displays have been opened, `disconnected` while the service is still trying to.
+    pub sign: &'static str,
+    /// `primary` or `backup`, whichever serial device the sign is on. Left out if there's no backup device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<&'static str>,
+    /// Number of times the sign has been switched to the other serial device. Left out if there's no backup device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failovers: Option<u32>,
+    /// Minutes the sign's clock was ahead of the host's, or behind if negative, when it was last checked. Left out
+    /// until it has been checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_drift_minutes: Option<i64>,
+}
+
+/// Handles a GET to `/status`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+///
+/// # Returns
+/// JSON with whether the sign is connected, if there is a backup serial device which device it is on, and how far the
+/// sign's clock has drifted if it has been checked.
+async fn status_handler(state: State<AppState>) -> Json<StatusResponse> {
+    let sign = if *state.connected.borrow() {
+        "connected"
+    } else {
+        "disconnected"
+    };
+    let port = state.failover.as_ref().map(|failover| {
+        if failover.on_backup() {
+            "backup"
+        } else {
+            "primary"
+        }
+    });
+    let failovers = state.failover.as_ref().map(|failover| failover.failovers());
+    let clock_drift_minutes = *state.clock_drift.borrow();
+    Json(StatusResponse {
+        sign,
+        port,
+        failovers,
+        clock_drift_minutes,
+    })
+}
+
+/// Handles a POST to `/scene/:name`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `name`: Name of the scene to play.
+///
+/// # Returns
+/// 200 once the scene has been queued, or 404 if there is no scene with that name in the config.
+async fn play_scene_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    let Some(frames) = state.config.borrow().scenes.get(&name).cloned() else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    if state
+        .send(&request_id, APICommand::PlayScene(frames))
+        .is_err()
+    {
+        tracing::error!("Sign loop has stopped, can't play scene");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    StatusCode::OK
+}
+
+/// Handles a POST to `/admin/reprovision`. Whether the sign's IR keyboard can edit the files follows the last PUT to
+/// `/admin/keyboard`, or `keyboard_access` in the config if there hasn't been one.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+///
+/// # Returns
+/// 200 once the sign's memory has been laid out as in the config, or 400 if the config has no memory layout.
+async fn reprovision_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+) -> StatusCode {
+    let keyboard_access = state.keyboard.lock().await.access;
+    reprovision(&state, &request_id, keyboard_access).await
+}
+
+/// Body for a PUT to `/admin/keyboard`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyboardRequest {
+    /// Whether the sign's IR keyboard can edit its files.
+    pub enabled: bool,
+}
+
+/// Handles a PUT to `/admin/keyboard`, locking the sign's IR keyboard out or letting it back in. The run sequence is
+/// sent again straight away with the new lock, but the sign only takes the lock on its files when its memory is laid
+/// out, so they keep theirs until the next POST to `/admin/reprovision` rather than being wiped here.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once any run sequence has been sent again and the lock recorded. If sending fails the lock is left as it was.
+async fn keyboard_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(KeyboardRequest { enabled }): Json<KeyboardRequest>,
+) -> StatusCode {
+    let mut keyboard = state.keyboard.lock().await;
+    if keyboard.access == enabled {
+        return StatusCode::OK;
+    }
+
+    // The lock is only recorded once the sign has taken it, so a failed attempt can be retried
+    if let Some(run_sequence) = &keyboard.run_sequence {
+        let mut run_sequence = run_sequence.clone();
+        run_sequence.keyboard_accessible = enabled;
+        let status = write_special(
+            &state,
+            &request_id,
+            WriteSpecial::SetRunSequence(run_sequence.clone()),
+        )
+        .await;
+        if status != StatusCode::OK {
+            return status;
+        }
+        keyboard.run_sequence = Some(run_sequence);
+    }
+    keyboard.access = enabled;
+    StatusCode::OK
+}
+
+/// Lays out the sign's memory as in the config.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `keyboard_access`: Whether the sign's IR keyboard can edit the files.
+///
+/// # Returns
+/// 200 once the sign's memory has been laid out, or 400 if the config has no memory layout.
+async fn reprovision(
+    state: &AppState,
+    request_id: &RequestId,
+    keyboard_access: bool,
+) -> StatusCode {
+    let files = state.config.borrow().memory.clone();
+    if files.is_empty() {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    let (tx, rx) = oneshot::channel::<APIResponse>();
+    if state
+        .send(
+            request_id,
+            APICommand::ConfigureMemory(files, keyboard_access, tx),
+        )
+        .is_err()
+    {
+        tracing::error!("Sign loop has stopped, can't reprovision");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    match rx.await {
+        Ok(APIResponse::Done) => StatusCode::OK,
+        Ok(_) | Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Runs one of the sign's special functions.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `special`: The special function to run.
+///
+/// # Returns
+/// 200 once the special function has been sent to the sign.
+async fn write_special(
+    state: &AppState,
+    request_id: &RequestId,
+    special: WriteSpecial,
+) -> StatusCode {
+    let (tx, rx) = oneshot::channel::<APIResponse>();
+    if state
+        .send(request_id, APICommand::WriteSpecial(special, tx))
+        .is_err()
+    {
+        tracing::error!("Sign loop has stopped, can't run special function");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    match rx.await {
+        Ok(APIResponse::Done) => StatusCode::OK,
+        Ok(_) | Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles a POST to `/admin/soft-reset`, restarting the sign without clearing its memory.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+///
+/// # Returns
+/// 200 once the reset has been sent to the sign.
+async fn soft_reset_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+) -> StatusCode {
+    write_special(
+        &state,
+        &request_id,
+        WriteSpecial::SoftReset(SoftReset::new()),
+    )
+    .await
+}
+
+/// Handles a POST to `/admin/clear-memory`, wiping every file on the sign.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+///
+/// # Returns
+/// 200 once the sign has been told to clear its memory.
+async fn clear_memory_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+) -> StatusCode {
+    write_special(
+        &state,
+        &request_id,
+        WriteSpecial::ClearMemoryAndFlash(ClearMemoryAndFlash::new()),
+    )
+    .await
+}
+
+/// How the sign picks which files to show in a run sequence.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunSequenceKind {
+    /// Show each file only during its run times.
+    FollowFileTimes,
+    /// Show every file, whatever its run times.
+    IgnoreFileTimes,
+    /// Show each file during its run times, deleting it once they end.
+    DeleteAtOffTime,
+}
+
+impl From<RunSequenceKind> for RunSequenceType {
+    fn from(kind: RunSequenceKind) -> Self {
+        match kind {
+            RunSequenceKind::FollowFileTimes => RunSequenceType::FollowFileTimes,
+            RunSequenceKind::IgnoreFileTimes => RunSequenceType::IgnoreFileTimes,
+            RunSequenceKind::DeleteAtOffTime => RunSequenceType::DeleteAtOffTime,
+        }
+    }
+}
+
+/// Body for a PUT to `/admin/run-sequence`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunSequenceRequest {
+    /// How the sign picks which files to show.
+    pub kind: RunSequenceKind,
+    /// Labels of the text files to show, in order.
+    pub labels: Vec<char>,
+}
+
+/// Handles a PUT to `/admin/run-sequence`, setting which text files the sign shows in turn. Whether the sign's IR
+/// keyboard can change the sequence follows the last PUT to `/admin/keyboard`, or `keyboard_access` in the config if
+/// there hasn't been one.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once the run sequence has been sent to the sign, or 400 if there are too many files or a label is invalid.
+async fn run_sequence_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(RunSequenceRequest { kind, labels }): Json<RunSequenceRequest>,
+) -> StatusCode {
+    if !labels.iter().all(char::is_ascii_graphic) {
+        return StatusCode::BAD_REQUEST;
+    }
+    let mut keyboard = state.keyboard.lock().await;
+    let Ok(run_sequence) = SetRunSequence::new(kind.into(), keyboard.access, labels) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    keyboard.run_sequence = Some(run_sequence.clone());
+    write_special(
+        &state,
+        &request_id,
+        WriteSpecial::SetRunSequence(run_sequence),
+    )
+    .await
+}
+
+/// Body for a PUT to `/admin/time-format`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeFormatRequest {
+    /// Whether the sign shows the time in 24 hour format rather than 12 hour.
+    pub twenty_four_hour: bool,
+}
+
+/// Handles a PUT to `/admin/time-format`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once the time format has been sent to the sign.
+async fn time_format_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(TimeFormatRequest { twenty_four_hour }): Json<TimeFormatRequest>,
+) -> StatusCode {
+    write_special(
+        &state,
+        &request_id,
+        WriteSpecial::SetTimeFormat(SetTimeFormat::new(twenty_four_hour)),
+    )
+    .await
+}
+
+/// Body for a PUT to `/admin/speaker`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpeakerRequest {
+    /// Whether the sign's speaker is on.
+    pub enabled: bool,
+}
+
+/// Handles a PUT to `/admin/speaker`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once the change has been sent to the sign.
+async fn speaker_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(SpeakerRequest { enabled }): Json<SpeakerRequest>,
+) -> StatusCode {
+    write_special(
+        &state,
+        &request_id,
+        WriteSpecial::ToggleSpeaker(ToggleSpeaker::new(enabled)),
+    )
+    .await
+}
+
+/// Body for a PUT to `/admin/night-mode`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NightModeRequest {
+    /// Whether the sign should show just the time.
+    pub enabled: bool,
+}
+
+/// Handles a PUT to `/admin/night-mode`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `request_id`: ID of the request.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once the change has been queued.
+async fn night_mode_handler(
+    state: State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(NightModeRequest { enabled }): Json<NightModeRequest>,
+) -> StatusCode {
+    let dimming = state.config.borrow().night.dimming_for(enabled);
+
+    if state
+        .send(&request_id, APICommand::NightMode(enabled, dimming))
+        .is_err()
+    {
+        tracing::error!("Sign loop has stopped, can't change night mode");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    StatusCode::OK
+}
+
+/// Body for a GET or PUT to `/admin/log-level`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogLevel {
+    /// Filter in the same form as `RUST_LOG`, e.g. `yhs_sign=debug,tower_http=trace`.
+    pub filter: String,
+}
+
+/// Handles a GET to `/admin/log-level`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+///
+/// # Returns
+/// JSON with the current log filter.
+async fn get_log_level_handler(state: State<AppState>) -> impl IntoResponse {
+    match state.log_filter.with_current(|filter| filter.to_string()) {
+        Ok(filter) => Json(LogLevel { filter }).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read log filter: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Handles a PUT to `/admin/log-level`, changing which logs are shown until the service restarts.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `body`: Request body.
+///
+/// # Returns
+/// 200 once the filter has been changed, or 400 if it isn't a valid filter.
+async fn put_log_level_handler(
+    state: State<AppState>,
+    Json(LogLevel { filter }): Json<LogLevel>,
+) -> StatusCode {
+    let Ok(env_filter) = EnvFilter::try_new(&filter) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if let Err(e) = state.log_filter.reload(env_filter) {
+        tracing::error!("Failed to change log filter: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    tracing::info!("Log filter changed to {filter}");
+    StatusCode::OK
+}
+
+/// Query parameters for a GET to `/current`.
+#[derive(Debug, Deserialize)]
+pub struct CurrentParams {
+    /// How long to wait for the text to change before responding, e.g. `30s`, or in seconds if there is no unit.
+    pub wait: Option<HumanDuration>,
+}
+
+/// Handles a GET to `/current`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `wait`: How long to wait for the displayed text to change.
+///
+/// # Returns
+/// JSON with the text on the sign once it changes or the wait is over, or 400 if `wait` can't be parsed.
+async fn current_handler(
+    state: State<AppState>,
+    Query(CurrentParams { wait }): Query<CurrentParams>,
+) -> impl IntoResponse {
+    let wait = wait.map_or(Duration::ZERO, |HumanDuration(wait)| wait.min(MAX_WAIT));
+
+    let mut displayed = state.displayed.clone();
+    displayed.borrow_and_update();
+    if !wait.is_zero() {
+        // Either way the current text is sent back, so there's nothing to do if the wait runs out.
+        tokio::time::timeout(wait, displayed.changed()).await.ok();
+    }
+
+    let current = displayed.borrow().clone();
+    Json(current).into_response()
+}
+
+/// Handles a GET to `/live`.
+///
+/// # Returns
+/// A page that mirrors what is currently on the sign.
+async fn live_handler() -> Html<&'static str> {
+    Html(include_str!("live.html"))
+}
+
+/// Handles a GET to `/live/ws`.
+///
+/// # Arguments
+/// * `state`: Shared application state.
+/// * `ws`: The WebSocket upgrade request.
+///
+/// # Returns
+/// A response upgrading the connection to a WebSocket that is sent the text currently on the sign whenever it changes.
+async fn live_ws_handler(state: State<AppState>, ws: WebSocketUpgrade) -> impl IntoResponse {
+    let displayed = state.displayed.clone();
+    ws.on_upgrade(move |socket| send_displayed_text(socket, displayed))
+}
+
+/// Sends the text currently on the sign down a WebSocket as JSON, then again every time it changes, until the client
+/// goes away.
+///
+/// # Arguments
+/// * `socket`: The WebSocket to send to.
+/// * `displayed`: Receiver for the text currently shown on the sign.
+async fn send_displayed_text(
+    mut socket: WebSocket,
+    mut displayed: watch::Receiver<Option<DisplayedText>>,
+) {
+    loop {
+        let json = match serde_json::to_string(&*displayed.borrow_and_update()) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::error!("Failed to serialise displayed text: {e}");
+                return;
+            }
+        };
+        if socket.send(Message::Text(json)).await.is_err() {
+            return;
+        }
+        if displayed.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, HttpBody};
+    use tower::ServiceExt;
+    use tracing_subscriber::reload;
+
+    use super::*;
+    use crate::config::Banner;
+
+    /// Makes an app around a config, with nothing listening for the commands it sends.
+    fn test_app(config: Config) -> Router {
+        test_app_with_commands(config).0
+    }
+
+    /// Makes an app around a config, along with the receiving end of the commands it sends.
+    fn test_app_with_commands(
+        config: Config,
+    ) -> (Router, tokio::sync::mpsc::UnboundedReceiver<QueuedCommand>) {
+        let (app, command_rx, _) = test_app_with_sign(config);
+        (app, command_rx)
+    }
+
+    /// Makes an app around a config, along with the receiving end of the commands it sends and a sender for the text
+    /// it reports is on the sign.
+    fn test_app_with_sign(
+        config: Config,
+    ) -> (
+        Router,
+        tokio::sync::mpsc::UnboundedReceiver<QueuedCommand>,
+        watch::Sender<Option<DisplayedText>>,
+    ) {
+        let (command_tx, command_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (_, config_rx) = watch::channel(config);
+        let (displayed_tx, displayed_rx) = watch::channel(None);
+        let (_, connected_rx) = watch::channel(false);
+        let (_, clock_drift_rx) = watch::channel(None);
+        let (log_layer, log_filter) = reload::Layer::new(EnvFilter::new("info"));
+        // The handle only works while its layer is alive
+        Box::leak(Box::new(log_layer));
+        let app = app(AppState::new(
+            command_tx,
+            config_rx,
+            displayed_rx,
+            connected_rx,
+            None,
+            clock_drift_rx,
+            log_filter,
+        ));
+        (app, command_rx, displayed_tx)
+    }
+
+    /// Makes a config with an admin token.
+    fn config_with_token() -> Config {
+        Config {
+            admin_token: Some("secret".to_string()),
+            ..Config::default()
+        }
+    }
+
+    /// Sends a GET, with the given `Authorization` header if there is one.
+    async fn get_status(app: Router, path: &str, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::get(path);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_needs_token() {
+        let app = test_app(config_with_token());
+        assert_eq!(
+            get_status(app.clone(), "/api/v1/admin/log-level", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app.clone(), "/api/v1/admin/log-level", Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app, "/api/v1/admin/nothing", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_with_token() {
+        let app = test_app(config_with_token());
+        assert_eq!(
+            get_status(app, "/api/v1/admin/log-level", Some("Bearer secret")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_refused_without_configured_token() {
+        let app = test_app(Config::default());
+        assert_eq!(
+            get_status(app, "/api/v1/admin/log-level", Some("Bearer secret")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_routes_need_no_token() {
+        let app = test_app(config_with_token());
+        assert_eq!(get_status(app, "/live", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_status_clock_drift() {
+        let (command_tx, _) = tokio::sync::mpsc::unbounded_channel();
+        let (_, config_rx) = watch::channel(Config::default());
+        let (_, displayed_rx) = watch::channel(None);
+        let (_, connected_rx) = watch::channel(true);
+        let (clock_drift_tx, clock_drift_rx) = watch::channel(None);
+        let (_, log_filter) = reload::Layer::new(EnvFilter::new("info"));
+        let state = AppState::new(
+            command_tx,
+            config_rx,
+            displayed_rx,
+            connected_rx,
+            None,
+            clock_drift_rx,
+            log_filter,
+        );
+
+        let Json(status) = status_handler(State(state.clone())).await;
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"sign":"connected"}"#
+        );
+        clock_drift_tx.send_replace(Some(-3));
+        let Json(status) = status_handler(State(state)).await;
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"sign":"connected","clock_drift_minutes":-3}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_banner_key() {
+        let config = Config {
+            text_keys: vec!["door".to_string(), "test".to_string()],
+            banner: Banner {
+                keys: vec!["door".to_string()],
+                ..Banner::default()
+            },
+            ..Config::default()
+        };
+        let (app, mut command_rx) = test_app_with_commands(config);
+        for key in ["door", "test"] {
+            let request = Request::put(format!("/api/v1/text/{key}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"text": "OPEN"}"#))
+                .unwrap();
+            let status = app.clone().oneshot(request).await.unwrap().status();
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        match command_rx.recv().await.unwrap().command {
+            APICommand::WriteBanner(text, _, picture) => {
+                assert_eq!(text.message, "OPEN");
+                assert_eq!(picture.label, 'Z');
+            }
+            _ => panic!("expected a banner"),
+        }
+        assert!(matches!(
+            command_rx.recv().await.unwrap().command,
+            APICommand::WriteText(..)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_admin_not_on_deprecated_paths() {
+        let app = test_app(config_with_token());
+        assert_eq!(
+            get_status(app, "/admin/log-level", Some("Bearer secret")).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    /// Sends a PUT of a JSON body to an admin route with the right token.
+    async fn put_admin(app: Router, path: &str, body: &str) -> StatusCode {
+        let request = Request::put(path)
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_keyboard_lock_kept_until_sign_takes_it() {
+        let (app, mut command_rx) = test_app_with_commands(config_with_token());
+        // Take the run sequence, fail the first change of lock, then take the retry
+        let sign = tokio::spawn(async move {
+            let mut sent = vec![];
+            for succeed in [true, false, true] {
+                let Some(QueuedCommand {
+                    command:
+                        APICommand::WriteSpecial(WriteSpecial::SetRunSequence(run_sequence), tx),
+                    ..
+                }) = command_rx.recv().await
+                else {
+                    panic!("expected a run sequence");
+                };
+                sent.push(run_sequence.keyboard_accessible);
+                if succeed {
+                    tx.send(APIResponse::Done).ok();
+                }
+            }
+            sent
+        });
+
+        let run_sequence = r#"{"kind": "follow_file_times", "labels": ["A"]}"#;
+        assert_eq!(
+            put_admin(app.clone(), "/api/v1/admin/run-sequence", run_sequence).await,
+            StatusCode::OK
+        );
+        let keyboard = r#"{"enabled": true}"#;
+        assert_eq!(
+            put_admin(app.clone(), "/api/v1/admin/keyboard", keyboard).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            put_admin(app, "/api/v1/admin/keyboard", keyboard).await,
+            StatusCode::OK
+        );
+        assert_eq!(sign.await.unwrap(), vec![false, true, true]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_refused() {
+        let body = format!(r#"{{"text": "{}"}}"#, "a".repeat(MAX_BODY_BYTES));
+        let request = Request::put("/api/v1/text/test")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = test_app(Config::default()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// Sends a GET for `/live` with the given `traceparent` header if there is one, returning the request ID echoed
+    /// back.
+    async fn request_id_for(traceparent: Option<&str>) -> String {
+        let mut request = Request::get("/live");
+        if let Some(traceparent) = traceparent {
+            request = request.header("traceparent", traceparent);
+        }
+        let response = test_app(Config::default())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_request_id_from_traceparent() {
+        assert_eq!(
+            request_id_for(Some(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+            ))
+            .await,
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_id_from_malformed_traceparent() {
+        for traceparent in [
+            "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+            "00-zzf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "nonsense",
+        ] {
+            let request_id = request_id_for(Some(traceparent)).await;
+            assert!(!traceparent.contains(&request_id));
+            // Falls back to a UUID
+            assert_eq!(request_id.len(), 36);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_id_without_traceparent() {
+        let request_id = request_id_for(None).await;
+        assert_eq!(request_id.len(), 36);
+        assert_ne!(request_id, request_id_for(None).await);
+    }
+
+    #[tokio::test]
+    async fn test_deprecation_header() {
+        let app = test_app(Config::default());
+        for (path, deprecated) in [("/api/v1/status", false), ("/status", true)] {
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("deprecation").is_some(),
+                deprecated,
+                "{path}"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_admin_timeout() {
+        // Hold on to the commands so the sign never answers
+        let (app, _command_rx) = test_app_with_commands(config_with_token());
+        let request = Request::post("/api/v1/admin/soft-reset")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let start = tokio::time::Instant::now();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(start.elapsed(), ADMIN_TIMEOUT);
+    }
+
+    /// Sends a GET to `/current`, returning how long it took to answer and the body.
+    async fn get_current(app: Router, path: &str) -> (Duration, String) {
+        let start = tokio::time::Instant::now();
+        let response = app
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let waited = start.elapsed();
+        let mut body = response.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (waited, String::from_utf8(bytes).unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_current_wait_runs_out() {
+        let (app, _command_rx, _displayed_tx) = test_app_with_sign(Config::default());
+        assert_eq!(
+            get_current(app.clone(), "/api/v1/current?wait=30s").await,
+            (Duration::from_secs(30), "null".to_string())
+        );
+        // Longer than the 10 second timeout for other requests, but no longer than MAX_WAIT
+        assert_eq!(
+            get_current(app, "/api/v1/current?wait=1h").await,
+            (MAX_WAIT, "null".to_string())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_current_wakes_on_change() {
+        let (app, _command_rx, displayed_tx) = test_app_with_sign(Config::default());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            displayed_tx.send_replace(Some(DisplayedText::from(&WriteText::new(
+                'A',
+                "hello".to_string(),
+            ))));
+            // Keep the sender alive, as the wait ends as soon as it is dropped
+            tokio::time::sleep(MAX_WAIT).await;
+        });
+        let (waited, body) = get_current(app, "/current?wait=30s").await;
+        assert_eq!(waited, Duration::from_secs(5));
+        assert!(body.contains(r#""text":"hello""#), "{body}");
+    }
+
+    #[tokio::test]
+    async fn test_current_deprecated_path() {
+        let app = test_app(Config::default());
+        let request = Request::get("/current").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key("deprecation"));
     }
 }